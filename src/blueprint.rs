@@ -0,0 +1,272 @@
+use std::{fs, io, path::PathBuf};
+
+use nannou::lyon::lyon_tessellation::Orientation;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    editor::{OrientationDef, Tool},
+    model::*,
+    paths,
+};
+
+/// Prefix of exported blueprint strings, so they're recognizable when pasted around
+const EXPORT_PREFIX: &str = "facto_rs-blueprint:";
+
+/// A copied layout, relative to the bottom left corner
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Blueprint {
+    pub name: String,
+    pub tiles: Vec<(Position, Piece)>,
+}
+
+/// What a blueprint puts on a tile. Buildings name their item, so that in a different world they
+/// make the same item, and can't be placed if it doesn't have one
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Piece {
+    Rail(#[serde(with = "OrientationDef")] Orientation),
+    Intersection(IntersectionType),
+    Building(String, Direction),
+    Generator,
+    PowerPole,
+}
+
+/// Blueprints saved to disk, one file each
+#[derive(Debug, Clone, Default)]
+pub struct Library {
+    pub blueprints: Vec<Blueprint>,
+}
+
+impl Blueprint {
+    /// Everything inside the rectangle between two corners, None if it's empty
+    pub fn copy(grid_items: &GridItems, a: Position, b: Position) -> Option<Blueprint> {
+        let (min, max) = (
+            Position(a.0.min(b.0), a.1.min(b.1)),
+            Position(a.0.max(b.0), a.1.max(b.1)),
+        );
+        let tiles: Vec<_> = grid_items
+            .range(Position(min.0, min.1)..=Position(max.0, max.1))
+            .filter(|(pos, _)| (min.1..=max.1).contains(&pos.1))
+            .map(|(pos, grid_item)| (*pos, Piece::of(grid_item)))
+            .collect();
+        if tiles.is_empty() {
+            return None;
+        }
+
+        Some(
+            Blueprint {
+                name: String::new(),
+                tiles,
+            }
+            .normalized(),
+        )
+    }
+
+    /// Turned a quarter clockwise
+    pub fn rotated(&self) -> Blueprint {
+        Blueprint {
+            name: self.name.clone(),
+            tiles: self
+                .tiles
+                .iter()
+                .map(|(pos, piece)| (Position(pos.1, -pos.0), piece.rotated()))
+                .collect(),
+        }
+        .normalized()
+    }
+
+    /// Flipped left to right
+    pub fn mirrored(&self) -> Blueprint {
+        Blueprint {
+            name: self.name.clone(),
+            tiles: self
+                .tiles
+                .iter()
+                .map(|(pos, piece)| (Position(-pos.0, pos.1), piece.mirrored()))
+                .collect(),
+        }
+        .normalized()
+    }
+
+    /// Where each tile goes when the bottom left corner is at `anchor`, and the tool that places
+    /// it in a world with these items. None for buildings of items the world doesn't have
+    pub fn placed_at<'a>(
+        &'a self,
+        anchor: Position,
+        items: &'a [Item],
+    ) -> impl Iterator<Item = (Position, Option<Tool>)> + 'a {
+        self.tiles.iter().map(move |(pos, piece)| {
+            (
+                Position(anchor.0 + pos.0, anchor.1 + pos.1),
+                piece.tool(items),
+            )
+        })
+    }
+
+    /// Single line that can be shared and imported again
+    pub fn export(&self) -> String {
+        format!(
+            "{EXPORT_PREFIX}{}",
+            serde_json::to_string(self).expect("blueprints always serialize")
+        )
+    }
+
+    pub fn import(text: &str) -> Option<Blueprint> {
+        let json = text.trim().strip_prefix(EXPORT_PREFIX)?;
+        serde_json::from_str(json).ok()
+    }
+
+    /// Moves the tiles so that the bottom left corner is at 0,0
+    fn normalized(mut self) -> Blueprint {
+        let min_x = self
+            .tiles
+            .iter()
+            .map(|(p, _)| p.0)
+            .min()
+            .unwrap_or_default();
+        let min_y = self
+            .tiles
+            .iter()
+            .map(|(p, _)| p.1)
+            .min()
+            .unwrap_or_default();
+        for (pos, _) in &mut self.tiles {
+            *pos = Position(pos.0 - min_x, pos.1 - min_y);
+        }
+        self.tiles.sort_by_key(|(pos, _)| *pos);
+        self
+    }
+}
+
+impl Library {
+    /// `blueprints` in the data directory
+    pub fn directory() -> io::Result<PathBuf> {
+        Ok(paths::data_dir()?.join("blueprints"))
+    }
+
+    /// Unreadable files are skipped
+    pub fn load() -> Library {
+        let Ok(entries) = Library::directory().and_then(fs::read_dir) else {
+            return Library::default();
+        };
+        let mut blueprints: Vec<Blueprint> = entries
+            .filter_map(|entry| fs::read_to_string(entry.ok()?.path()).ok())
+            .filter_map(|text| serde_json::from_str(&text).ok())
+            .collect();
+        blueprints.sort_by(|a, b| a.name.cmp(&b.name));
+        Library { blueprints }
+    }
+
+    /// Adds it under a new name and writes it to disk, returns the file it's in
+    pub fn save(&mut self, mut blueprint: Blueprint) -> io::Result<PathBuf> {
+        let mut number = self.blueprints.len() + 1;
+        while self
+            .blueprints
+            .iter()
+            .any(|b| b.name == format!("blueprint-{number}"))
+        {
+            number += 1;
+        }
+        blueprint.name = format!("blueprint-{number}");
+
+        let directory = Library::directory()?;
+        fs::create_dir_all(&directory)?;
+        let path = directory.join(format!("{}.json", blueprint.name));
+        fs::write(&path, serde_json::to_string_pretty(&blueprint)?)?;
+        self.blueprints.push(blueprint);
+        Ok(path)
+    }
+}
+
+/// Adds an exported blueprint string to the library. Returns the process exit code
+pub fn import(text: &str) -> i32 {
+    let Some(blueprint) = Blueprint::import(text) else {
+        eprintln!("Not a valid blueprint string");
+        return 1;
+    };
+    let mut library = Library::load();
+    match library.save(blueprint) {
+        Ok(path) => {
+            let name = &library.blueprints.last().unwrap().name;
+            println!("Imported as {name} to {}", path.display());
+            0
+        }
+        Err(e) => {
+            eprintln!("Failed to save blueprint: {e}");
+            1
+        }
+    }
+}
+
+impl GridItem {
+    /// The tool that places this, None if it can't be placed
+    pub fn tool(&self, items: &[Item]) -> Option<Tool> {
+        match self {
+            GridItem::Building(b, d) => {
+                let idx = items.iter().position(|i| i == b.item())?;
+                Some(Tool::Building(idx, *d))
+            }
+            GridItem::Rail(orientation) => Some(Tool::Rail(*orientation)),
+            GridItem::Intersection(intersection_type) => {
+                Some(Tool::Intersection(*intersection_type))
+            }
+            GridItem::Generator => Some(Tool::Generator),
+            GridItem::PowerPole => Some(Tool::PowerPole),
+        }
+    }
+}
+
+impl Piece {
+    fn of(grid_item: &GridItem) -> Piece {
+        match grid_item {
+            GridItem::Building(b, d) => Piece::Building(b.item().name.clone(), *d),
+            GridItem::Rail(orientation) => Piece::Rail(*orientation),
+            GridItem::Intersection(intersection_type) => Piece::Intersection(*intersection_type),
+            GridItem::Generator => Piece::Generator,
+            GridItem::PowerPole => Piece::PowerPole,
+        }
+    }
+
+    /// The tool that places this in a world with these items
+    fn tool(&self, items: &[Item]) -> Option<Tool> {
+        match self {
+            Piece::Building(name, d) => {
+                let idx = items.iter().position(|i| &i.name == name)?;
+                Some(Tool::Building(idx, *d))
+            }
+            Piece::Rail(orientation) => Some(Tool::Rail(*orientation)),
+            Piece::Intersection(intersection_type) => Some(Tool::Intersection(*intersection_type)),
+            Piece::Generator => Some(Tool::Generator),
+            Piece::PowerPole => Some(Tool::PowerPole),
+        }
+    }
+
+    /// Same as `GridItem::rotated`, but for what the blueprint places
+    fn rotated(&self) -> Piece {
+        match self {
+            Piece::Rail(Orientation::Horizontal) => Piece::Rail(Orientation::Vertical),
+            Piece::Rail(Orientation::Vertical) => Piece::Rail(Orientation::Horizontal),
+            Piece::Intersection(IntersectionType::Corner(d)) => {
+                Piece::Intersection(IntersectionType::Corner(d.right()))
+            }
+            Piece::Intersection(IntersectionType::Triple(d)) => {
+                Piece::Intersection(IntersectionType::Triple(d.right()))
+            }
+            Piece::Building(name, d) => Piece::Building(name.clone(), d.right()),
+            piece => piece.clone(),
+        }
+    }
+
+    fn mirrored(&self) -> Piece {
+        match self {
+            Piece::Building(name, d) => Piece::Building(name.clone(), d.mirrored()),
+            Piece::Intersection(IntersectionType::Triple(d)) => {
+                Piece::Intersection(IntersectionType::Triple(d.mirrored()))
+            }
+            // Corner(d) connects d and d.right(), those two swap sides when mirrored
+            Piece::Intersection(IntersectionType::Corner(d)) => {
+                Piece::Intersection(IntersectionType::Corner(d.mirrored().left()))
+            }
+            piece => piece.clone(),
+        }
+    }
+}
@@ -1,41 +1,48 @@
-use std::{collections::VecDeque, ops::Deref};
-
-use nannou::prelude::*;
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, VecDeque},
+};
 
 use crate::{constants::*, model::*, train::calculate_path};
 
 impl Building {
+    #[allow(clippy::too_many_arguments)]
     pub fn update(
         &self,
         position: &Position,
-        update: &Update,
+        dt: f64,
         grid_items: &GridItems,
         trains: &mut VecDeque<Train>,
         score: &mut usize,
+        balance: &mut usize,
+        power: f64,
     ) {
+        let animation_dt = dt;
+        let dt = dt * self.tier().speed * power;
         match self {
             Building::Spawner {
                 item,
                 timer,
                 spawn_timer,
+                shipped,
+                ..
             } => {
                 let mut timer = timer.borrow_mut();
                 let mut spawn_timer = spawn_timer.borrow_mut();
-                if *timer > item.time && !Building::train_full(*position, trains) {
-                    if let Some(target) = find_train_target(item, grid_items, trains) {
-                        *timer = 0.0;
+                if *timer > self.recipe().time && !Building::train_full(*position, trains) {
+                    if let Some(path) = find_train_path(*position, item, grid_items, trains) {
+                        self.ship(&mut timer, &mut shipped.borrow_mut());
                         *spawn_timer = 0.0;
                         trains.push_back(Train {
-                            item: item.clone(),
-                            path: calculate_path(*position, target, grid_items),
+                            item: Some(item.clone()),
+                            path,
                             position: 0,
                             sub_position: 0.5,
                         });
                     }
                 } else {
-                    *timer += update.since_last.secs();
-                    *spawn_timer =
-                        ITEM_SPAWN_ANIMATION_TIME.min(*spawn_timer + update.since_last.secs());
+                    *timer += dt;
+                    *spawn_timer = ITEM_SPAWN_ANIMATION_TIME.min(*spawn_timer + animation_dt);
                 }
             }
             Building::Crafter {
@@ -43,35 +50,42 @@ impl Building {
                 contents,
                 timer,
                 spawn_timer,
+                shipped,
+                ..
             } => {
+                let recipe = self.recipe();
                 let mut timer = timer.borrow_mut();
                 let mut spawn_timer = spawn_timer.borrow_mut();
-                if *timer > item.time && !Building::train_full(*position, trains) {
-                    if let Some(target) = find_train_target(item, grid_items, trains) {
-                        *timer = 0.0;
+                if *timer > recipe.time && !Building::train_full(*position, trains) {
+                    if let Some(path) = find_train_path(*position, item, grid_items, trains) {
+                        self.ship(&mut timer, &mut shipped.borrow_mut());
                         *spawn_timer = 0.0;
                         trains.push_back(Train {
-                            item: item.clone(),
-                            path: calculate_path(*position, target, grid_items),
+                            item: Some(item.clone()),
+                            path,
                             position: 0,
                             sub_position: 0.5,
                         });
                     }
                     return; // don't tick spawn timer
-                } else if *timer == 0.0 && &item.components == contents.borrow().deref() {
-                    // Only start if we have contents, consuming them in the process
-                    contents.borrow_mut().clear();
-                    *timer += update.since_last.secs();
+                } else if *timer == 0.0
+                    && dt > 0.0
+                    && has_components(&contents.borrow(), &recipe.components)
+                {
+                    // Only start if we have contents and power, consuming them in the process
+                    consume_components(&mut contents.borrow_mut(), &recipe.components);
+                    *timer += dt;
                 } else if *timer > 0.0 {
-                    *timer += update.since_last.secs();
+                    *timer += dt;
                 }
-                *spawn_timer =
-                    ITEM_SPAWN_ANIMATION_TIME.min(*spawn_timer + update.since_last.secs());
+                *spawn_timer = ITEM_SPAWN_ANIMATION_TIME.min(*spawn_timer + animation_dt);
             }
-            Building::Submitter { item, contents } => {
-                if &item.components == contents.borrow().deref() {
-                    contents.borrow_mut().clear();
+            Building::Submitter { contents, .. } => {
+                let components = &self.recipe().components;
+                if has_components(&contents.borrow(), components) {
+                    consume_components(&mut contents.borrow_mut(), components);
                     *score += 1;
+                    *balance += POINT_REWARD;
                 }
             }
         }
@@ -85,12 +99,14 @@ impl Building {
     ) -> bool {
         match self {
             Building::Spawner { .. } => false,
-            Building::Crafter { item, contents, .. } | Building::Submitter { item, contents } => {
-                let desired_count = item
+            Building::Crafter { contents, .. } | Building::Submitter { contents, .. } => {
+                let desired_count = self
+                    .recipe()
                     .components
                     .get(target_item)
                     .copied()
-                    .unwrap_or_default();
+                    .unwrap_or_default()
+                    * self.tier().buffer;
                 let existing_count = contents
                     .borrow()
                     .get(target_item)
@@ -98,7 +114,10 @@ impl Building {
                     .unwrap_or_default();
                 let incoming_trains = trains
                     .iter()
-                    .filter(|t| t.path.last().unwrap() == self_position && &t.item == target_item)
+                    .filter(|t| {
+                        t.path.last().unwrap() == self_position
+                            && t.item.as_ref() == Some(target_item)
+                    })
                     .count();
 
                 existing_count + incoming_trains < desired_count
@@ -106,24 +125,151 @@ impl Building {
         }
     }
 
-    fn train_full(position: Position, trains: &VecDeque<Train>) -> bool {
+    pub fn item(&self) -> &Item {
+        match self {
+            Building::Spawner { item, .. }
+            | Building::Crafter { item, .. }
+            | Building::Submitter { item, .. } => item,
+        }
+    }
+
+    /// The recipe this building is currently producing with
+    pub fn recipe(&self) -> &Recipe {
+        match self {
+            Building::Spawner { item, .. } => &item.recipes[0],
+            Building::Crafter { item, recipe, .. } | Building::Submitter { item, recipe, .. } => {
+                &item.recipes[*recipe.borrow()]
+            }
+        }
+    }
+
+    pub fn new(item: &Item, is_point: bool, recipe: usize) -> Building {
+        if item.is_spawnable() {
+            Building::Spawner {
+                item: item.clone(),
+                tier: RefCell::new(0),
+                timer: RefCell::new(0.0),
+                spawn_timer: RefCell::new(ITEM_SPAWN_ANIMATION_TIME),
+                shipped: RefCell::new(0),
+            }
+        } else if is_point {
+            // points' buildings are actually submitters
+            Building::Submitter {
+                item: item.clone(),
+                recipe: RefCell::new(recipe),
+                contents: RefCell::new(BTreeMap::new()),
+            }
+        } else {
+            Building::Crafter {
+                item: item.clone(),
+                recipe: RefCell::new(recipe),
+                tier: RefCell::new(0),
+                contents: RefCell::new(BTreeMap::new()),
+                timer: RefCell::new(0.0),
+                spawn_timer: RefCell::new(ITEM_SPAWN_ANIMATION_TIME),
+                shipped: RefCell::new(0),
+            }
+        }
+    }
+
+    pub fn tier(&self) -> &'static BuildingTier {
+        match self {
+            Building::Spawner { tier, .. } | Building::Crafter { tier, .. } => {
+                &TIERS[*tier.borrow()]
+            }
+            Building::Submitter { .. } => &TIERS[0],
+        }
+    }
+
+    /// Cost of upgrading to the next tier, None if already at the top or not upgradable
+    pub fn upgrade_cost(&self) -> Option<usize> {
+        match self {
+            Building::Spawner { tier, .. } | Building::Crafter { tier, .. } => {
+                TIERS.get(*tier.borrow() + 1).map(|t| t.cost)
+            }
+            Building::Submitter { .. } => None,
+        }
+    }
+
+    /// Goes up or down a tier, paying for it is up to the caller
+    pub fn change_tier(&self, up: bool) {
+        if let Building::Spawner { tier, .. } | Building::Crafter { tier, .. } = self {
+            let mut tier = tier.borrow_mut();
+            *tier = if up { *tier + 1 } else { *tier - 1 };
+        }
+    }
+
+    /// Switches to the item's next alternative recipe, dropping contents the new recipe doesn't use
+    pub fn cycle_recipe(&self) {
+        if let Building::Crafter {
+            item,
+            recipe,
+            contents,
+            ..
+        }
+        | Building::Submitter {
+            item,
+            recipe,
+            contents,
+        } = self
+        {
+            let mut recipe = recipe.borrow_mut();
+            *recipe = (*recipe + 1) % item.recipes.len();
+
+            let components = &item.recipes[*recipe].components;
+            let buffer = self.tier().buffer;
+            let mut contents = contents.borrow_mut();
+            contents.retain(|item, count| {
+                *count = (*count).min(components.get(item).copied().unwrap_or_default() * buffer);
+                *count > 0
+            });
+        }
+    }
+
+    /// Called when an item is sent out, the timer only restarts once the whole batch is out
+    fn ship(&self, timer: &mut f64, shipped: &mut usize) {
+        *shipped += 1;
+        if *shipped >= self.tier().output {
+            *shipped = 0;
+            *timer = 0.0;
+        }
+    }
+
+    pub fn train_full(position: Position, trains: &VecDeque<Train>) -> bool {
         trains.iter().any(|t| t.path[t.position] == position)
     }
 }
 
 // === Utils ===
 
-fn find_train_target(
+fn has_components(contents: &BTreeMap<Item, usize>, components: &BTreeMap<Item, usize>) -> bool {
+    components
+        .iter()
+        .all(|(item, count)| contents.get(item).copied().unwrap_or_default() >= *count)
+}
+
+fn consume_components(contents: &mut BTreeMap<Item, usize>, components: &BTreeMap<Item, usize>) {
+    for (item, count) in components {
+        let existing = contents.get_mut(item).expect("checked by has_components");
+        *existing -= count;
+        if *existing == 0 {
+            contents.remove(item);
+        }
+    }
+}
+
+/// Path to the first reachable building that needs the item
+fn find_train_path(
+    start: Position,
     item: &Item,
     grid_items: &GridItems,
     trains: &VecDeque<Train>,
-) -> Option<Position> {
-    for (pos, grid_item) in grid_items {
-        if let GridItem::Building(b, _) = grid_item {
-            if b.requires(item, pos, trains) {
-                return Some(*pos);
-            }
-        }
-    }
-    None
+) -> Option<Vec<Position>> {
+    grid_items
+        .iter()
+        .filter(|(pos, grid_item)| match grid_item {
+            GridItem::Building(b, _) => b.requires(item, pos, trains),
+            _ => false,
+        })
+        .find_map(|(pos, _)| calculate_path(start, *pos, grid_items))
 }
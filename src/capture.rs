@@ -0,0 +1,75 @@
+use std::{
+    io,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::paths;
+
+/// Pictures of the window, taken with the screenshot command or from the command line
+#[derive(Debug, Clone)]
+pub struct Capture {
+    /// Taken once the game is on screen, or once the replay ends
+    pub screenshot: Option<PathBuf>,
+    /// Some while recording, the directory frames are saved to
+    pub frames: Option<PathBuf>,
+    /// Game ticks between recorded frames
+    pub frame_ticks: u64,
+    /// Frames recorded so far
+    pub frame: u64,
+    /// Started from the command line, the app closes when the capture is done
+    pub exit_when_done: bool,
+    /// Set once the last capture was requested, to close after it's written
+    pub exiting: bool,
+}
+
+impl Default for Capture {
+    fn default() -> Self {
+        Capture {
+            screenshot: None,
+            frames: None,
+            frame_ticks: 1,
+            frame: 0,
+            exit_when_done: false,
+            exiting: false,
+        }
+    }
+}
+
+impl Capture {
+    /// Path of the next recorded frame, numbered so they sort in order
+    pub fn next_frame(&mut self) -> Option<PathBuf> {
+        let path = self
+            .frames
+            .as_ref()?
+            .join(format!("frame-{:06}.png", self.frame));
+        self.frame += 1;
+        Some(path)
+    }
+
+    /// Starts recording into a new directory, or stops
+    pub fn toggle_recording(&mut self) -> io::Result<()> {
+        self.frames = match self.frames {
+            Some(_) => None,
+            None => Some(directory()?.join(format!("frames-{}", timestamp()))),
+        };
+        self.frame = 0;
+        Ok(())
+    }
+}
+
+/// `captures` in the data directory
+pub fn directory() -> io::Result<PathBuf> {
+    Ok(paths::data_dir()?.join("captures"))
+}
+
+/// A new file in the captures directory
+pub fn screenshot_path() -> io::Result<PathBuf> {
+    Ok(directory()?.join(format!("screenshot-{}.png", timestamp())))
+}
+
+fn timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
@@ -0,0 +1,244 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    constants::*,
+    content::ContentPack,
+    generate::{Difficulty, RecipeConstraints},
+    layout::Layout,
+    reliability::{Reliability, Repair},
+    settings::Settings,
+    style::Style,
+};
+
+/// Command line options, see `USAGE`
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    pub game: GameOptions,
+    /// Where to save a replay of this session when the window closes
+    pub record: Option<PathBuf>,
+    /// Replay to watch instead of playing
+    pub replay: Option<PathBuf>,
+    /// Replay to check without opening a window
+    pub verify: Option<PathBuf>,
+    /// Exported blueprint string to add to the library
+    pub import_blueprint: Option<String>,
+    /// Picture to save of the game once it's on screen, or of the replay once it ends
+    pub screenshot: Option<PathBuf>,
+    /// Directory to save every frame to
+    pub frames: Option<PathBuf>,
+    /// Game ticks between saved frames
+    pub frame_ticks: Option<u64>,
+    /// Where to render the world as an SVG instead of opening a window
+    pub render_svg: Option<PathBuf>,
+    /// Seconds of game time simulated before rendering
+    pub time: f64,
+    pub style: Style,
+}
+
+/// Everything the world is generated from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GameOptions {
+    pub seed: u64,
+    /// Buildings need to be in reach of a powered pole to run
+    pub power: bool,
+    /// Buildings and intersections break down randomly
+    pub reliability: Option<Reliability>,
+    #[serde(default)]
+    pub layout: Layout,
+    #[serde(default)]
+    pub recipes: RecipeConstraints,
+    /// Hand-made items, kept in full so that replays don't depend on the file
+    #[serde(default)]
+    pub content: Option<ContentPack>,
+}
+
+impl Default for GameOptions {
+    fn default() -> Self {
+        GameOptions {
+            seed: DEFAULT_SEED,
+            power: false,
+            reliability: None,
+            layout: Layout::default(),
+            recipes: RecipeConstraints::default(),
+            content: None,
+        }
+    }
+}
+
+const USAGE: &str = "\
+Usage: facto_rs [OPTIONS]
+
+Options:
+    --seed <SEED>            Seed for generating the world
+    --layout <LAYOUT>        How the world is laid out: plus, grid, ring, spine or districts
+    --content <FILE>         Load items and recipes from a content pack
+    --difficulty <PRESET>    Recipe constraints preset: easy, normal or hard
+    --min-items <COUNT>      Fewest items in the recipe tree
+    --max-items <COUNT>      Most items in the recipe tree
+    --raw-resources <COUNT>  Items made by spawners
+    --max-depth <STEPS>      Most crafting steps from a raw resource
+    --max-fan-in <COUNT>     Most different components in one recipe
+    --max-raw-demand <COUNT> Most raw resources needed for one point
+    --power                  Enable the power network
+    --reliability            Enable random breakdowns
+    --failure-rate <RATE>    Breakdown chance per second for each building and intersection
+    --repair <auto|train>    Repair automatically after a delay, or with maintenance trains
+    --repair-time <SECONDS>  Delay for automatic repairs, and for those no train can get to
+    --palette <PALETTE>      Item colors: normal, deuteranopia, protanopia or tritanopia
+    --labels                 Write labels on item icons
+    --theme <THEME|FILE>     dark, light, high-contrast or a theme file, reloaded when it changes
+    --record <FILE>          Save a replay when the window is closed
+    --replay <FILE>          Watch a replay
+    --verify <FILE>          Re-simulate a replay without a window and check its final score
+    --import-blueprint <TEXT>  Add an exported blueprint string to the library
+    --screenshot <FILE>      Save a PNG of the game once it's on screen, or of a replay once it ends,
+                             and exit
+    --frames <DIR>           Save every frame as a numbered PNG, exiting when a replay ends
+    --frame-ticks <TICKS>    Game ticks between saved frames, however long they take to render
+    --render-svg <FILE>      Render the world to an SVG without a window: the one generated from the
+                             options above, or the --replay one
+    --time <SECONDS>         Game time to simulate before rendering
+    --help                   Print this message";
+
+impl Options {
+    /// Starts from the saved settings
+    pub fn from_args(settings: &Settings) -> Options {
+        let mut options = Options::default();
+        options.game.layout = settings.generator.layout;
+        options.game.power = settings.generator.power;
+        options.game.recipes = settings.generator.recipes.clone();
+        options.style.palette = settings.palette;
+        options.style.labels = settings.labels;
+        if let Err(e) = options.style.load_theme(&settings.theme) {
+            eprintln!("Ignoring theme {} from the settings: {e}", settings.theme);
+        }
+        let mut reliability = settings
+            .generator
+            .reliability
+            .clone()
+            .unwrap_or(Reliability {
+                failure_rate: DEFAULT_FAILURE_RATE,
+                repair: Repair::Auto,
+                repair_time: DEFAULT_REPAIR_TIME,
+            });
+        let mut reliability_enabled = settings.generator.reliability.is_some();
+
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--seed" => options.game.seed = parse_value(&arg, args.next()),
+                "--layout" => {
+                    options.game.layout =
+                        args.next().and_then(|v| v.parse().ok()).unwrap_or_else(|| {
+                            fail(&format!(
+                                "{arg} expects plus, grid, ring, spine or districts"
+                            ))
+                        })
+                }
+                "--content" => {
+                    let path = parse_path(&arg, args.next());
+                    match ContentPack::load(&path) {
+                        Ok(pack) => options.game.content = Some(pack),
+                        Err(e) => fail(&format!("Invalid content pack {}: {e}", path.display())),
+                    }
+                }
+                "--difficulty" => {
+                    let difficulty: Difficulty = args
+                        .next()
+                        .and_then(|v| v.parse().ok())
+                        .unwrap_or_else(|| fail(&format!("{arg} expects easy, normal or hard")));
+                    options.game.recipes = difficulty.constraints();
+                }
+                "--min-items" => options.game.recipes.min_items = parse_value(&arg, args.next()),
+                "--max-items" => options.game.recipes.max_items = parse_value(&arg, args.next()),
+                "--raw-resources" => {
+                    options.game.recipes.raw_resources = parse_value(&arg, args.next())
+                }
+                "--max-depth" => options.game.recipes.max_depth = parse_value(&arg, args.next()),
+                "--max-fan-in" => options.game.recipes.max_fan_in = parse_value(&arg, args.next()),
+                "--max-raw-demand" => {
+                    options.game.recipes.max_raw_demand = parse_value(&arg, args.next())
+                }
+                "--power" => options.game.power = true,
+                "--reliability" => reliability_enabled = true,
+                "--failure-rate" => {
+                    reliability_enabled = true;
+                    reliability.failure_rate = parse_value(&arg, args.next());
+                }
+                "--repair" => {
+                    reliability_enabled = true;
+                    reliability.repair = match args.next().as_deref() {
+                        Some("auto") => Repair::Auto,
+                        Some("train") => Repair::Maintenance,
+                        _ => fail(&format!("{arg} expects auto or train")),
+                    };
+                }
+                "--repair-time" => {
+                    reliability_enabled = true;
+                    reliability.repair_time = parse_value(&arg, args.next());
+                }
+                "--palette" => {
+                    options.style.palette =
+                        args.next().and_then(|v| v.parse().ok()).unwrap_or_else(|| {
+                            fail(&format!(
+                                "{arg} expects normal, deuteranopia, protanopia or tritanopia"
+                            ))
+                        })
+                }
+                "--labels" => options.style.labels = true,
+                "--theme" => {
+                    let value = args
+                        .next()
+                        .unwrap_or_else(|| fail(&format!("{arg} expects a theme or a file")));
+                    if let Err(e) = options.style.load_theme(&value) {
+                        fail(&format!("Invalid theme {value}: {e}"));
+                    }
+                }
+                "--record" => options.record = Some(parse_path(&arg, args.next())),
+                "--replay" => options.replay = Some(parse_path(&arg, args.next())),
+                "--verify" => options.verify = Some(parse_path(&arg, args.next())),
+                "--screenshot" => options.screenshot = Some(parse_path(&arg, args.next())),
+                "--frames" => options.frames = Some(parse_path(&arg, args.next())),
+                "--frame-ticks" => options.frame_ticks = Some(parse_value(&arg, args.next())),
+                "--render-svg" => options.render_svg = Some(parse_path(&arg, args.next())),
+                "--time" => options.time = parse_value(&arg, args.next()),
+                "--import-blueprint" => {
+                    options.import_blueprint = Some(
+                        args.next()
+                            .unwrap_or_else(|| fail("--import-blueprint expects text")),
+                    )
+                }
+                "--help" => {
+                    println!("{USAGE}");
+                    std::process::exit(0);
+                }
+                other => fail(&format!("Unknown argument: {other}")),
+            }
+        }
+
+        options.game.reliability = reliability_enabled.then_some(reliability);
+        if let Err(e) = options.game.recipes.validate() {
+            fail(&format!("Invalid recipe constraints: {e}"));
+        }
+        options
+    }
+}
+
+fn parse_value<T: std::str::FromStr>(arg: &str, value: Option<String>) -> T {
+    value
+        .and_then(|v| v.parse().ok())
+        .unwrap_or_else(|| fail(&format!("{arg} expects a number")))
+}
+
+fn parse_path(arg: &str, value: Option<String>) -> PathBuf {
+    value
+        .map(PathBuf::from)
+        .unwrap_or_else(|| fail(&format!("{arg} expects a file")))
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("{message}\n\n{USAGE}");
+    std::process::exit(1);
+}
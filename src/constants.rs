@@ -1,4 +1,9 @@
-use nannou::prelude::*;
+use crate::model::BuildingTier;
+
+// Window
+/// Size when windowed, until resized
+pub const DEFAULT_WINDOW_WIDTH: u32 = 1280;
+pub const DEFAULT_WINDOW_HEIGHT: u32 = 800;
 
 // Grid
 pub const SCREEN_GRID_PADDING: isize = 5;
@@ -7,33 +12,146 @@ pub const CELL_SIZE: f32 = SIZE_UNIT * 100.0;
 pub const BUILDING_SIZE: f32 = CELL_SIZE / 3.0 * 2.0;
 
 pub const LOADING_BAR_WEIGHT: f64 = 10.0;
-pub const LOADING_BAR_COLOR: Rgba<u8> = Rgba {
-    color: Rgb {
-        red: 60,
-        green: 60,
-        blue: 60,
-        standard: ::core::marker::PhantomData,
-    },
-    alpha: 100,
-};
 pub const INVENTORY_ITEM_SQUARE_SIDE: usize = 3;
+pub const RECIPE_DOT_SIZE: f32 = 6.0 * SIZE_UNIT;
 pub const ITEM_SPAWN_ANIMATION_TIME: f64 = 0.2;
 pub const ITEM_SPAWN_ANIMATION_TIME_SHRINK: f64 = ITEM_SPAWN_ANIMATION_TIME * 0.7;
 
+/// Item icons on crafters
+pub const ICON_SIZE: f32 = 14.0 * SIZE_UNIT;
+pub const TIER_MARK_SIZE: f32 = 8.0 * SIZE_UNIT;
+/// The exclamation mark on broken tiles
+pub const BREAKDOWN_MARK_SIZE: f32 = CELL_SIZE / 4.0;
+
 pub const SLOT_LENGTH: f64 = 0.3;
 pub const TRAIN_BOUNDARY_1: f64 = SLOT_LENGTH;
 pub const TRAIN_BOUNDARY_2: f64 = 1.0 - SLOT_LENGTH;
 
 pub const TRAIN_LENGTH: f64 = 0.2;
+/// How close to a train the mouse has to be to hover it
+pub const TRAIN_HOVER_RADIUS: f32 = CELL_SIZE * TRAIN_LENGTH as f32;
+pub const ROUTE_WIDTH: f32 = CELL_SIZE / 10.0;
+pub const ROUTE_ALPHA: f32 = 0.6;
+
+// Camera
+/// Zoom factor of one wheel step
+pub const ZOOM_STEP: f32 = 1.25;
+pub const MIN_ZOOM: f32 = 0.25;
+pub const MAX_ZOOM: f32 = 8.0;
+
+// Minimap
+pub const MINIMAP_SIZE: f32 = 200.0;
+pub const MINIMAP_PADDING: f32 = 20.0;
+/// Smallest train dot, in pixels
+pub const MINIMAP_TRAIN_SIZE: f32 = 3.0;
+
+// Traffic
+pub const TRAFFIC_BUCKET_TICKS: u64 = 60;
+/// Buckets of traffic kept for the time windows
+pub const TRAFFIC_HISTORY: usize = 600;
+/// Opacity of the busiest cell of the heatmap
+pub const TRAFFIC_ALPHA: f32 = 0.6;
+/// Buckets the heatmap can count over, None for everything since the last reset
+pub const TRAFFIC_WINDOWS: [Option<usize>; 4] = [None, Some(30), Some(120), Some(TRAFFIC_HISTORY)];
+
+// Menus
+pub const MENU_WIDTH: f32 = 600.0;
+pub const MENU_ROW_HEIGHT: f32 = 40.0;
+pub const HELP_ROW_HEIGHT: f32 = 26.0;
+pub const HELP_COLUMN_WIDTH: f32 = 420.0;
+pub const STATISTICS_WIDTH: f32 = 480.0;
+pub const STATISTICS_ROW_HEIGHT: f32 = 30.0;
 
 // Recipes
 pub const ITEM_RECIPE_SIZE: f32 = 40.0;
 pub const RECIPE_ROW_HEIGHT: f32 = ITEM_RECIPE_SIZE * 1.5;
 
+// Simulation
+/// Seconds simulated in one step, fixed so that replays are deterministic
+pub const TICK_LENGTH: f64 = 1.0 / 60.0;
+/// Past this the simulation falls behind instead of freezing the window
+pub const MAX_TICKS_PER_FRAME: usize = 100;
+pub const MAX_SPEED: f64 = 16.0;
+/// How far the arrow keys jump in a replay
+pub const SEEK_TICKS: u64 = 10 * 60;
+
 // Generation
+pub const DEFAULT_SEED: u64 = 1;
 pub const MIN_ITEMS: usize = 6;
 pub const MAX_ITEMS: usize = 10;
 pub const MAX_SPAWNABLE_ITEMS: usize = 3;
 pub const MAX_COMPONENTS: usize = 5;
+pub const MAX_RECIPES: usize = 3;
+pub const ALTERNATIVE_RECIPE_CHANCE: f64 = 0.4;
+/// Recipe trees tried before settling for the one closest to the constraints
+pub const MAX_RECIPE_ATTEMPTS: usize = 100;
+/// Chance that a spur to a building turns a corner before reaching it
+pub const BEND_CHANCE: f64 = 0.5;
 pub const MIN_ITEM_TIME: f64 = 1.0;
 pub const MAX_ITEM_TIME: f64 = 5.0;
+
+// Layouts
+/// Tries at finding a free spot for a building before giving up
+pub const MAX_PLACEMENT_ATTEMPTS: usize = 1000;
+/// Length of each arm of the plus layout's main lines, at least
+pub const MIN_PLUS_SIZE: isize = 3;
+/// Distance between streets in the grid layout
+pub const BLOCK_SIZE: isize = 3;
+/// Distance between branches in the spine layout
+pub const BRANCH_SPACING: isize = 3;
+pub const MIN_BRANCH_LENGTH: isize = 2;
+pub const MAX_BRANCH_LENGTH: isize = 5;
+/// Buildings per district
+pub const DISTRICT_SIZE: usize = 8;
+pub const DISTRICT_WIDTH: isize = 4;
+pub const DISTRICT_HEIGHT: isize = 3;
+/// From the main line to the closest side of a district
+pub const DISTRICT_DISTANCE: isize = 3;
+/// Between districts on the same side of the main line
+pub const DISTRICT_SPACING: isize = 7;
+
+// Buildings
+pub const TIERS: [BuildingTier; 3] = [
+    BuildingTier {
+        speed: 1.0,
+        buffer: 1,
+        output: 1,
+        cost: 0,
+    },
+    BuildingTier {
+        speed: 1.5,
+        buffer: 2,
+        output: 1,
+        cost: 5,
+    },
+    BuildingTier {
+        speed: 2.0,
+        buffer: 3,
+        output: 2,
+        cost: 20,
+    },
+];
+
+// Economy
+pub const STARTING_BALANCE: usize = 50;
+pub const POINT_REWARD: usize = 10;
+pub const RAIL_COST: usize = 1;
+pub const INTERSECTION_COST: usize = 5;
+pub const BUILDING_COST: usize = 20;
+pub const GENERATOR_COST: usize = 30;
+pub const POWER_POLE_COST: usize = 5;
+/// Portion of the cost returned when removing something
+pub const REFUND_RATIO: f64 = 0.5;
+
+// Power
+/// In cells
+pub const POWER_POLE_RADIUS: isize = 3;
+pub const GENERATOR_OUTPUT: f64 = 10.0;
+pub const SPAWNER_POWER_USAGE: f64 = 1.0;
+pub const CRAFTER_POWER_USAGE: f64 = 2.0;
+
+// Reliability
+pub const DEFAULT_FAILURE_RATE: f64 = 0.005;
+pub const DEFAULT_REPAIR_TIME: f64 = 10.0;
+/// Seconds before looking for a maintenance train again, after none could get to a breakdown
+pub const DISPATCH_RETRY_TIME: f64 = 1.0;
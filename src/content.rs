@@ -0,0 +1,231 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use nannou::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::model::*;
+
+/// Hand-made items and recipes, read from a TOML file. Either used as they are, or with
+/// `generate` only lending their names, colors and shapes to randomly generated items
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContentPack {
+    pub name: String,
+    /// Generate recipes like without a pack, the items here only decide what they look like
+    #[serde(default)]
+    pub generate: bool,
+    /// Replaces the usual starting balance
+    #[serde(default)]
+    pub starting_balance: Option<usize>,
+    /// Components have to be declared before the items made from them. The last one is the
+    /// item that gets submitted for points
+    pub items: Vec<ItemDefinition>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ItemDefinition {
+    pub name: String,
+    /// Written on the icon when labels are on, the first two letters of the name if not given
+    #[serde(default)]
+    pub label: Option<String>,
+    /// `#rrggbb`
+    pub color: String,
+    #[serde(default)]
+    pub shape: Shape,
+    /// Seconds to spawn one, for raw resources
+    #[serde(default)]
+    pub time: Option<f64>,
+    /// None for raw resources. The first one is the default
+    #[serde(default)]
+    pub recipes: Vec<RecipeDefinition>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecipeDefinition {
+    /// Seconds to craft one
+    pub time: f64,
+    /// Item name --> how many
+    pub components: BTreeMap<String, usize>,
+}
+
+impl ContentPack {
+    pub fn load(path: &Path) -> Result<ContentPack, String> {
+        let text = fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let pack: ContentPack = toml::from_str(&text).map_err(|e| e.to_string())?;
+        pack.validate()?;
+        Ok(pack)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.generate {
+            // Only the looks are used, but replays save them as items
+            return (0..self.items.len())
+                .try_for_each(|idx| validate_looks(&self.items[..idx], &self.items[idx]));
+        }
+        validate(&self.items)
+    }
+
+    /// The pack's own items and recipes
+    pub fn items(&self) -> Vec<Item> {
+        items(&self.items)
+    }
+
+    /// Gives generated items the pack's looks, in order. Items past the end of the pack keep
+    /// theirs
+    pub fn skin(&self, items: &mut [Item]) {
+        for (item, definition) in items.iter_mut().zip(&self.items) {
+            item.name = definition.name.clone();
+            item.label = definition.label();
+            item.color = parse_color(&definition.color).unwrap();
+            item.shape = definition.shape;
+        }
+        // Components are copies, so they need the new looks too. They always come before the
+        // items made from them
+        for idx in 0..items.len() {
+            let (before, after) = items.split_at_mut(idx);
+            for recipe in &mut after[0].recipes {
+                recipe.components = std::mem::take(&mut recipe.components)
+                    .into_iter()
+                    .map(|(component, count)| {
+                        let skinned = before.iter().find(|i| i.id == component.id).unwrap();
+                        (skinned.clone(), count)
+                    })
+                    .collect();
+            }
+        }
+    }
+}
+
+/// Describes the first problem that would make the items unusable
+pub fn validate(items: &[ItemDefinition]) -> Result<(), String> {
+    let Some(point) = items.last() else {
+        return Err("there are no items".to_string());
+    };
+    if point.recipes.is_empty() {
+        return Err(format!("{}, the last item, has no recipe", point.name));
+    }
+    for (idx, item) in items.iter().enumerate() {
+        let declared = &items[..idx];
+        validate_looks(declared, item)?;
+        match (&item.time, item.recipes.is_empty()) {
+            (None, true) => return Err(format!("{} needs a time or a recipe", item.name)),
+            (Some(_), false) => return Err(format!("{} has both a time and recipes", item.name)),
+            _ => {}
+        }
+        for recipe in &item.recipes {
+            if recipe.components.is_empty() {
+                return Err(format!("{} has a recipe without components", item.name));
+            }
+            for component in recipe.components.keys() {
+                if !declared.iter().any(|i| &i.name == component) {
+                    return Err(format!(
+                        "{} needs {component}, which isn't declared before it",
+                        item.name
+                    ));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Checks what the item looks like, and that its name isn't taken by one declared before it
+fn validate_looks(declared: &[ItemDefinition], item: &ItemDefinition) -> Result<(), String> {
+    parse_color(&item.color)?;
+    if declared.iter().any(|i| i.name == item.name) {
+        return Err(format!("{} is declared twice", item.name));
+    }
+    Ok(())
+}
+
+/// The items and recipes of the definitions
+pub fn items(definitions: &[ItemDefinition]) -> Vec<Item> {
+    let mut items: Vec<Item> = vec![];
+    for (id, definition) in definitions.iter().enumerate() {
+        let recipes = match definition.time {
+            Some(time) => vec![Recipe {
+                components: BTreeMap::new(),
+                time,
+            }],
+            None => definition
+                .recipes
+                .iter()
+                .map(|recipe| Recipe {
+                    components: recipe
+                        .components
+                        .iter()
+                        .map(|(name, &count)| {
+                            let component = items.iter().find(|i| &i.name == name).unwrap();
+                            (component.clone(), count)
+                        })
+                        .collect(),
+                    time: recipe.time,
+                })
+                .collect(),
+        };
+        items.push(Item {
+            id,
+            name: definition.name.clone(),
+            label: definition.label(),
+            color: parse_color(&definition.color).unwrap(),
+            shape: definition.shape,
+            recipes,
+        });
+    }
+    items
+}
+
+impl ItemDefinition {
+    /// Defines an item the way it is, so it can be made again without generating it
+    pub fn of(item: &Item) -> ItemDefinition {
+        let recipe_definition = |recipe: &Recipe| RecipeDefinition {
+            time: recipe.time,
+            components: recipe
+                .components
+                .iter()
+                .map(|(component, &count)| (component.name.clone(), count))
+                .collect(),
+        };
+        ItemDefinition {
+            name: item.name.clone(),
+            label: Some(item.label.clone()),
+            color: format_color(item.color),
+            shape: item.shape,
+            time: item.is_spawnable().then(|| item.recipes[0].time),
+            recipes: if item.is_spawnable() {
+                vec![]
+            } else {
+                item.recipes.iter().map(recipe_definition).collect()
+            },
+        }
+    }
+
+    fn label(&self) -> String {
+        self.label
+            .clone()
+            .unwrap_or_else(|| self.name.chars().take(2).collect())
+    }
+}
+
+fn format_color(color: Srgb) -> String {
+    let channel = |c: f32| (c * 255.0).round() as u8;
+    format!(
+        "#{:02x}{:02x}{:02x}",
+        channel(color.red),
+        channel(color.green),
+        channel(color.blue)
+    )
+}
+
+fn parse_color(text: &str) -> Result<Srgb, String> {
+    let invalid = || format!("{text} isn't a #rrggbb color");
+    let hex = text.strip_prefix('#').ok_or_else(invalid)?;
+    if hex.len() != 6 || !hex.is_ascii() {
+        return Err(invalid());
+    }
+    let channel = |i: usize| {
+        u8::from_str_radix(&hex[i..i + 2], 16)
+            .map(|c| c as f32 / 255.0)
+            .map_err(|_| invalid())
+    };
+    Ok(Srgb::new(channel(0)?, channel(2)?, channel(4)?))
+}
@@ -0,0 +1,317 @@
+use std::collections::VecDeque;
+
+use nannou::lyon::lyon_tessellation::Orientation;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    blueprint::{Blueprint, Library},
+    constants::*,
+    game::{Action, Game},
+    model::*,
+};
+
+#[derive(Debug, Clone, Default)]
+pub struct Editor {
+    pub tool: Tool,
+    /// Where the tool was last used while the mouse is held down
+    pub last_used: Option<Position>,
+    /// Corners of the rectangle picked with the select tool
+    pub selection: Option<(Position, Position)>,
+    /// What the paste tool places
+    pub clipboard: Option<Blueprint>,
+    pub library: Library,
+    /// Tiles dragged over with the rail tool. The last one is only laid once the drag moves on
+    /// or ends, when it's known where the rail leaves it
+    pub rail_path: Vec<Position>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub enum Tool {
+    /// Left click cycles recipes, right click upgrades
+    #[default]
+    Inspect,
+    Rail(#[serde(with = "OrientationDef")] Orientation),
+    Intersection(IntersectionType),
+    /// Index into the item list
+    Building(usize, Direction),
+    Generator,
+    PowerPole,
+    /// Turns whatever is on the tile
+    Rotate,
+    Remove,
+    /// Drag out a rectangle to copy. Only used by the editor, never applied to the game
+    Select,
+    /// Places the clipboard. Only used by the editor, never applied to the game
+    Paste,
+}
+
+impl Tool {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Tool::Inspect => "Inspect",
+            Tool::Rail(..) => "Rail",
+            Tool::Intersection(..) => "Intersection",
+            Tool::Building(..) => "Building",
+            Tool::Generator => "Generator",
+            Tool::PowerPole => "Power pole",
+            Tool::Rotate => "Rotate",
+            Tool::Remove => "Remove",
+            Tool::Select => "Select",
+            Tool::Paste => "Paste",
+        }
+    }
+
+    pub fn rotate(&mut self) {
+        match self {
+            Tool::Rail(orientation) => {
+                *orientation = match orientation {
+                    Orientation::Horizontal => Orientation::Vertical,
+                    Orientation::Vertical => Orientation::Horizontal,
+                }
+            }
+            Tool::Intersection(intersection_type) => {
+                // Go through all rotations of a type before moving on to the next one
+                *intersection_type = match *intersection_type {
+                    IntersectionType::Corner(Direction::South) => {
+                        IntersectionType::Triple(Direction::North)
+                    }
+                    IntersectionType::Corner(d) => IntersectionType::Corner(d.right()),
+                    IntersectionType::Triple(Direction::West) => IntersectionType::Quad,
+                    IntersectionType::Triple(d) => IntersectionType::Triple(d.right()),
+                    IntersectionType::Quad => IntersectionType::Corner(Direction::North),
+                }
+            }
+            Tool::Building(_, direction) => *direction = direction.right(),
+            Tool::Inspect
+            | Tool::Generator
+            | Tool::PowerPole
+            | Tool::Rotate
+            | Tool::Remove
+            | Tool::Select
+            | Tool::Paste => {}
+        }
+    }
+
+    /// Whether holding the mouse down keeps using it on every tile the cursor passes
+    pub fn is_draggable(&self) -> bool {
+        matches!(
+            self,
+            Tool::Rail(..) | Tool::Intersection(..) | Tool::PowerPole | Tool::Remove
+        )
+    }
+
+    /// Switches the building tool to the next item
+    pub fn next_item(&mut self, items: &[Item]) {
+        if let Tool::Building(idx, _) = self {
+            *idx = (*idx + 1) % items.len();
+        }
+    }
+
+    /// What clicking with this tool would place
+    pub fn grid_item(&self, items: &[Item]) -> Option<GridItem> {
+        match *self {
+            Tool::Rail(orientation) => Some(GridItem::Rail(orientation)),
+            Tool::Intersection(intersection_type) => {
+                Some(GridItem::Intersection(intersection_type))
+            }
+            Tool::Building(idx, direction) => Some(GridItem::Building(
+                Building::new(items.get(idx)?, idx + 1 == items.len(), 0),
+                direction,
+            )),
+            Tool::Generator => Some(GridItem::Generator),
+            Tool::PowerPole => Some(GridItem::PowerPole),
+            Tool::Inspect | Tool::Rotate | Tool::Remove | Tool::Select | Tool::Paste => None,
+        }
+    }
+}
+
+impl GridItem {
+    pub fn cost(&self) -> usize {
+        match self {
+            GridItem::Building(..) => BUILDING_COST,
+            GridItem::Rail(..) => RAIL_COST,
+            GridItem::Intersection(..) => INTERSECTION_COST,
+            GridItem::Generator => GENERATOR_COST,
+            GridItem::PowerPole => POWER_POLE_COST,
+        }
+    }
+
+    pub fn refund(&self) -> usize {
+        (self.cost() as f64 * REFUND_RATIO) as usize
+    }
+
+    /// Directions trains can leave this tile in, None if it isn't a rail or intersection
+    pub fn rail_connections(&self) -> Option<Vec<Direction>> {
+        match self {
+            GridItem::Rail(orientation) => Some(match orientation {
+                Orientation::Vertical => vec![Direction::North, Direction::South],
+                Orientation::Horizontal => vec![Direction::East, Direction::West],
+            }),
+            GridItem::Intersection(IntersectionType::Corner(d)) => Some(vec![*d, d.right()]),
+            GridItem::Intersection(IntersectionType::Triple(d)) => {
+                Some(vec![*d, d.left(), d.right()])
+            }
+            GridItem::Intersection(IntersectionType::Quad) => Some(DIRECTIONS.to_vec()),
+            _ => None,
+        }
+    }
+
+    /// The rail or intersection that connects exactly these directions. A single direction
+    /// gives a straight rail that ends on the far side
+    pub fn joining(connections: &[Direction]) -> GridItem {
+        let connected: Vec<_> = DIRECTIONS
+            .into_iter()
+            .filter(|d| connections.contains(d))
+            .collect();
+        match connected[..] {
+            [d] => GridItem::Rail(d.to_orientation()),
+            [a, b] if a.opposite() == b => GridItem::Rail(a.to_orientation()),
+            [a, b] if a.right() == b => GridItem::Intersection(IntersectionType::Corner(a)),
+            [_, b] => GridItem::Intersection(IntersectionType::Corner(b)),
+            [_, _, _] => {
+                let missing = DIRECTIONS
+                    .into_iter()
+                    .find(|d| !connected.contains(d))
+                    .unwrap();
+                GridItem::Intersection(IntersectionType::Triple(missing.opposite()))
+            }
+            _ => GridItem::Intersection(IntersectionType::Quad),
+        }
+    }
+
+    /// Turned a quarter clockwise, None if turning doesn't change anything
+    pub fn rotated(&self) -> Option<GridItem> {
+        match self {
+            GridItem::Building(b, d) => Some(GridItem::Building(b.clone(), d.right())),
+            GridItem::Rail(orientation) => Some(GridItem::Rail(match orientation {
+                Orientation::Horizontal => Orientation::Vertical,
+                Orientation::Vertical => Orientation::Horizontal,
+            })),
+            GridItem::Intersection(IntersectionType::Corner(d)) => {
+                Some(GridItem::Intersection(IntersectionType::Corner(d.right())))
+            }
+            GridItem::Intersection(IntersectionType::Triple(d)) => {
+                Some(GridItem::Intersection(IntersectionType::Triple(d.right())))
+            }
+            GridItem::Intersection(IntersectionType::Quad)
+            | GridItem::Generator
+            | GridItem::PowerPole => None,
+        }
+    }
+}
+
+impl Editor {
+    pub fn copy_selection(&mut self, game: &Game) {
+        let Some((a, b)) = self.selection else {
+            return;
+        };
+        if let Some(blueprint) = Blueprint::copy(&game.grid.grid_items, a, b) {
+            self.clipboard = Some(blueprint);
+            self.selection = None;
+            self.tool = Tool::Paste;
+        }
+    }
+
+    /// Puts the next (or previous) saved blueprint on the clipboard
+    pub fn cycle_library(&mut self, forward: bool) {
+        let count = self.library.blueprints.len();
+        if count == 0 {
+            return;
+        }
+        let current = self.clipboard.as_ref().and_then(|c| {
+            self.library
+                .blueprints
+                .iter()
+                .position(|b| b.name == c.name)
+        });
+        let next = match (current, forward) {
+            (None, _) => 0,
+            (Some(i), true) => (i + 1) % count,
+            (Some(i), false) => (i + count - 1) % count,
+        };
+        self.clipboard = Some(self.library.blueprints[next].clone());
+        self.tool = Tool::Paste;
+    }
+
+    /// Extends the rail being dragged to `target`, one tile at a time so that fast mouse moves
+    /// don't leave gaps. Returns the actions that lay the tiles that were passed
+    pub fn drag_rail(&mut self, target: Position) -> Vec<Action> {
+        let mut actions = vec![];
+        while let Some(&tip) = self.rail_path.last() {
+            if tip == target {
+                break;
+            }
+            let next = if tip.0 != target.0 {
+                Position(tip.0 + (target.0 - tip.0).signum(), tip.1)
+            } else {
+                Position(tip.0, tip.1 + (target.1 - tip.1).signum())
+            };
+            let exit = tip.direction_towards(next).unwrap();
+            actions.push(self.lay_tip(Some(exit)));
+            self.rail_path.push(next);
+        }
+        actions
+    }
+
+    /// Ends the drag. A click without dragging just places a rail of the tool's orientation
+    pub fn finish_rail(&mut self) -> Option<Action> {
+        let action = match (self.rail_path.len(), self.tool) {
+            (0, _) => None,
+            (1, Tool::Rail(orientation)) => {
+                Some(Action::UseTool(Tool::Rail(orientation), self.rail_path[0]))
+            }
+            _ => Some(self.lay_tip(None)),
+        };
+        self.rail_path.clear();
+        action
+    }
+
+    /// Connects the tip of the path to where it came from and to `exit`
+    fn lay_tip(&self, exit: Option<Direction>) -> Action {
+        let tip = self.rail_path[self.rail_path.len() - 1];
+        let entry = match self.rail_path.len() {
+            1 => None,
+            len => tip.direction_towards(self.rail_path[len - 2]),
+        };
+        match (entry, exit) {
+            (Some(entry), exit) => Action::LayRail(tip, entry, exit),
+            (None, Some(exit)) => Action::LayRail(tip, exit, None),
+            (None, None) => unreachable!("the start is only laid once the path leaves it"),
+        }
+    }
+}
+
+/// Whether the tool can be used on this position
+pub fn can_use(game: &Game, tool: Tool, position: Position) -> bool {
+    let grid_items = &game.grid.grid_items;
+    match tool.grid_item(&game.items) {
+        Some(grid_item) => !grid_items.contains_key(&position) && grid_item.cost() <= game.balance,
+        None => match tool {
+            Tool::Remove => {
+                grid_items.contains_key(&position) && !occupied(position, &game.grid.trains)
+            }
+            Tool::Rotate => {
+                grid_items
+                    .get(&position)
+                    .and_then(GridItem::rotated)
+                    .is_some()
+                    && !occupied(position, &game.grid.trains)
+            }
+            // The item doesn't exist in this world
+            Tool::Building(..) | Tool::Select | Tool::Paste => false,
+            _ => matches!(grid_items.get(&position), Some(GridItem::Building(..))),
+        },
+    }
+}
+
+/// Tiles with a train on them can't change, trains that only pass through later get rerouted
+pub fn occupied(position: Position, trains: &VecDeque<Train>) -> bool {
+    trains.iter().any(|t| t.path[t.position] == position)
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "Orientation")]
+pub enum OrientationDef {
+    Vertical,
+    Horizontal,
+}
@@ -0,0 +1,348 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use rand::{rngs::StdRng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cli::GameOptions,
+    constants::*,
+    editor::{self, Tool},
+    generate,
+    history::{Change, Edit, History},
+    model::*,
+    power::PowerNetwork,
+    reliability::Reliability,
+    statistics::Statistics,
+    traffic::Traffic,
+};
+
+/// Everything that gets simulated, kept apart from the window so it can also run headless
+#[derive(Debug, Clone)]
+pub struct Game {
+    pub grid: Grid,
+    pub items: Vec<Item>,
+    pub score: usize,
+    /// Currency earned from submitting, spent on building and upgrades
+    pub balance: usize,
+    /// None when playing without power
+    pub power: Option<PowerNetwork>,
+    /// None when buildings never break
+    pub reliability: Option<Reliability>,
+    /// Steps of TICK_LENGTH simulated so far
+    pub tick: u64,
+    pub history: History,
+    pub traffic: Traffic,
+    pub statistics: Statistics,
+}
+
+/// A player input. Besides stepping, these are the only way the game changes
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum Action {
+    /// Left click with a tool
+    UseTool(Tool, Position),
+    /// Dragging the rail tool over a tile: connects it in the first direction and, if given, the
+    /// second. Rails and intersections already there are joined into the fitting intersection
+    LayRail(Position, Direction, Option<Direction>),
+    /// Right click, buys the next tier for a building
+    Upgrade(Position),
+    /// Groups the following edits into one undo step, until `EndBatch`
+    BeginBatch,
+    EndBatch,
+    Undo,
+    Redo,
+}
+
+impl Game {
+    pub fn new(options: &GameOptions) -> Game {
+        let (grid_items, items) = generate::generate(options);
+        Game::with_world(options, grid_items, items)
+    }
+
+    /// A game on a world that was made beforehand, like the map saved in a replay
+    pub fn with_world(options: &GameOptions, grid_items: GridItems, items: Vec<Item>) -> Game {
+        let grid = Grid {
+            grid_items,
+            trains: VecDeque::new(),
+            breakdowns: BTreeMap::new(),
+            rng: StdRng::seed_from_u64(options.seed),
+        };
+        let power = options
+            .power
+            .then(|| PowerNetwork::calculate(&grid.grid_items));
+        Game {
+            grid,
+            items,
+            score: 0,
+            balance: options
+                .content
+                .as_ref()
+                .and_then(|c| c.starting_balance)
+                .unwrap_or(STARTING_BALANCE),
+            power,
+            reliability: options.reliability.clone(),
+            tick: 0,
+            history: History::default(),
+            traffic: Traffic::default(),
+            statistics: Statistics::default(),
+        }
+    }
+
+    pub fn step(&mut self) {
+        let dt = TICK_LENGTH;
+        let grid = &mut self.grid;
+
+        for _ in 0..grid.trains.len() {
+            let mut train = grid.trains.pop_front().unwrap();
+            if train.update(
+                dt,
+                &mut grid.grid_items,
+                &mut grid.trains,
+                &mut grid.breakdowns,
+                &mut self.traffic,
+            ) {
+                grid.trains.push_back(train);
+            }
+        }
+
+        if let Some(reliability) = &self.reliability {
+            reliability.update(grid, dt);
+        }
+
+        if self.power.is_some() {
+            self.power = Some(PowerNetwork::calculate(&grid.grid_items));
+        }
+
+        let waiting = grid.trains.len();
+        for (pos, grid_item) in &grid.grid_items {
+            if grid.breakdowns.contains_key(pos) {
+                continue;
+            }
+            let power = self.power.as_ref().map_or(1.0, |p| p.factor(*pos));
+            grid_item.update(
+                pos,
+                dt,
+                &grid.grid_items,
+                &mut grid.trains,
+                &mut self.score,
+                &mut self.balance,
+                power,
+            );
+        }
+
+        // Trains added after the buildings updated were just shipped
+        for train in grid.trains.range(waiting..) {
+            if let Some(item) = &train.item {
+                self.statistics.ship(item);
+            }
+        }
+
+        self.traffic.tick();
+        self.statistics.tick();
+        self.tick += 1;
+    }
+
+    /// Returns true if the action did anything
+    pub fn apply(&mut self, action: &Action) -> bool {
+        match *action {
+            Action::UseTool(tool, position) => {
+                if !editor::can_use(self, tool, position) {
+                    return false;
+                }
+
+                let before = self.grid.grid_items.get(&position);
+                let (change, balance_change) = match tool {
+                    Tool::Inspect => {
+                        if let Some(GridItem::Building(b, _)) = before {
+                            b.cycle_recipe();
+                        }
+                        return true;
+                    }
+                    Tool::Remove => (Change::replacing(None), before.unwrap().refund() as isize),
+                    Tool::Rotate => (Change::Rotate, 0),
+                    tool => {
+                        let grid_item = tool.grid_item(&self.items).unwrap();
+                        let cost = grid_item.cost() as isize;
+                        (Change::replacing(Some(grid_item)), -cost)
+                    }
+                };
+
+                self.record_edit(position, change, balance_change);
+                true
+            }
+            Action::LayRail(position, entry, exit) => {
+                let before = self.grid.grid_items.get(&position);
+                let mut connections = match before {
+                    Some(grid_item) => match grid_item.rail_connections() {
+                        Some(connections) => connections,
+                        None => return false,
+                    },
+                    None => vec![],
+                };
+                let joined = connections.contains(&entry)
+                    && exit.is_none_or(|exit| connections.contains(&exit));
+                if joined {
+                    return true;
+                }
+                connections.extend([Some(entry), exit].into_iter().flatten());
+
+                let after = GridItem::joining(&connections);
+                // Upgrading only costs the difference
+                let cost = after.cost() - before.map_or(0, GridItem::cost);
+                let occupied = before.is_some() && editor::occupied(position, &self.grid.trains);
+                if cost > self.balance || occupied {
+                    return false;
+                }
+                self.record_edit(position, Change::replacing(Some(after)), -(cost as isize));
+                true
+            }
+            Action::Upgrade(position) => {
+                let Some(GridItem::Building(b, _)) = self.grid.grid_items.get(&position) else {
+                    return false;
+                };
+                match b.upgrade_cost() {
+                    Some(cost) if cost <= self.balance => {
+                        self.record_edit(position, Change::Upgrade, -(cost as isize));
+                        true
+                    }
+                    _ => false,
+                }
+            }
+            Action::BeginBatch => {
+                self.history.begin_batch();
+                true
+            }
+            Action::EndBatch => {
+                self.history.end_batch();
+                true
+            }
+            Action::Undo => {
+                let Some(mut batch) = self.history.take_undo() else {
+                    return false;
+                };
+                if !self.can_make_edits(batch.iter().rev(), true) {
+                    self.history.push_undo(batch);
+                    return false;
+                }
+                for edit in batch.iter_mut().rev() {
+                    self.make_edit(edit, true);
+                }
+                self.history.push_redo(batch);
+                true
+            }
+            Action::Redo => {
+                let Some(mut batch) = self.history.take_redo() else {
+                    return false;
+                };
+                if !self.can_make_edits(batch.iter(), false) {
+                    self.history.push_redo(batch);
+                    return false;
+                }
+                for edit in &mut batch {
+                    self.make_edit(edit, false);
+                }
+                self.history.push_undo(batch);
+                true
+            }
+        }
+    }
+
+    fn record_edit(&mut self, position: Position, change: Change, balance_change: isize) {
+        let mut edit = Edit {
+            position,
+            change,
+            balance_change,
+        };
+        self.make_edit(&mut edit, false);
+        self.history.record(edit);
+    }
+
+    /// Undoing or redoing is only possible if it's affordable and no train sits on the tiles it
+    /// changes the rails of
+    fn can_make_edits<'a>(&self, edits: impl Iterator<Item = &'a Edit>, reverse: bool) -> bool {
+        let mut balance = self.balance as isize;
+        for edit in edits {
+            balance += if reverse {
+                -edit.balance_change
+            } else {
+                edit.balance_change
+            };
+            let moves_rails = !matches!(edit.change, Change::Upgrade);
+            if balance < 0 || (moves_rails && editor::occupied(edit.position, &self.grid.trains)) {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Applies the edit to the tile, or reverts it if `reverse`
+    fn make_edit(&mut self, edit: &mut Edit, reverse: bool) {
+        let balance_change = if reverse {
+            -edit.balance_change
+        } else {
+            edit.balance_change
+        };
+        self.balance = self
+            .balance
+            .checked_add_signed(balance_change)
+            .expect("edits are checked to be affordable");
+
+        let grid = &mut self.grid;
+        match &mut edit.change {
+            Change::Replace { before, after } => {
+                let (put, taken) = if reverse {
+                    (before, after)
+                } else {
+                    (after, before)
+                };
+                *taken = match put.take() {
+                    Some(grid_item) => grid.grid_items.insert(edit.position, grid_item),
+                    None => {
+                        grid.breakdowns.remove(&edit.position);
+                        grid.grid_items.remove(&edit.position)
+                    }
+                };
+            }
+            Change::Rotate => {
+                let grid_item = grid
+                    .grid_items
+                    .get_mut(&edit.position)
+                    .expect("rotated tiles stay until the rotation is undone");
+                // Three quarter turns clockwise make one back
+                let turns = if reverse { 3 } else { 1 };
+                for _ in 0..turns {
+                    *grid_item = grid_item
+                        .rotated()
+                        .expect("only turnable tiles are rotated");
+                }
+            }
+            Change::Upgrade => {
+                if let Some(GridItem::Building(b, _)) = grid.grid_items.get(&edit.position) {
+                    b.change_tier(!reverse);
+                }
+                return;
+            }
+        }
+        grid.reroute_trains(edit.position);
+    }
+}
+
+impl Grid {
+    /// Finds new paths for trains that were going through a changed tile, trains that can't get
+    /// where they were going anymore are dropped
+    pub fn reroute_trains(&mut self, changed: Position) {
+        let grid_items = &self.grid_items;
+        let breakdowns = &mut self.breakdowns;
+        self.trains.retain_mut(|train| {
+            if !train.path[train.position..].contains(&changed) || train.reroute(grid_items) {
+                return true;
+            }
+            // Someone else will have to come fix it
+            if let Some(breakdown) = breakdowns.get_mut(train.path.last().unwrap()) {
+                if train.item.is_none() {
+                    breakdown.dispatched = false;
+                }
+            }
+            false
+        });
+    }
+}
@@ -1,27 +1,193 @@
-use std::{
-    cell::RefCell,
-    collections::{BTreeMap, VecDeque},
-};
+use std::collections::{BTreeMap, BTreeSet};
 
-use nannou::{color::Hue, lyon::lyon_tessellation::Orientation, prelude::*};
+use nannou::{color::Hue, prelude::*};
 use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
 
-use crate::{constants::*, model::*};
-
-pub fn generate() -> (Grid, Vec<Item>) {
-    let mut rng = StdRng::seed_from_u64(1);
-    let items = generate_recipes(&mut rng);
-    let grid_items = generate_grid_items(&items, &mut rng);
+use crate::{
+    cli::GameOptions,
+    constants::*,
+    layout::{self, Layout, LayoutError},
+    model::*,
+    power::{self, PowerNetwork},
+};
 
-    let grid = Grid {
-        grid_items,
-        trains: VecDeque::new(),
+pub fn generate(options: &GameOptions) -> (GridItems, Vec<Item>) {
+    let mut rng = StdRng::seed_from_u64(options.seed);
+    let mut items = match &options.content {
+        Some(pack) if !pack.generate => pack.items(),
+        _ => generate_recipes(&options.recipes, &mut rng),
+    };
+    if let Some(pack) = options.content.as_ref().filter(|p| p.generate) {
+        pack.skin(&mut items);
+    }
+    let buildings = buildings_for(&items);
+    let mut grid_items = match generate_layout(options.layout, buildings.clone(), &mut rng) {
+        Ok(grid_items) => grid_items,
+        Err(e) => {
+            eprintln!(
+                "Couldn't generate the {} layout ({e}), using the grid layout instead",
+                options.layout
+            );
+            generate_layout(Layout::Grid, buildings, &mut rng)
+                .expect("the grid layout is made big enough for every building")
+        }
     };
-    (grid, items)
+    if options.power {
+        generate_power(&mut grid_items);
+    }
+    (grid_items, items)
+}
+
+/// Limits on the shape of the recipe tree
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecipeConstraints {
+    /// Items generated, before dropping the ones the point doesn't need
+    pub min_items: usize,
+    pub max_items: usize,
+    /// Items with nothing in their recipe, made by spawners
+    pub raw_resources: usize,
+    /// Most crafting steps between a raw resource and anything made from it
+    pub max_depth: usize,
+    /// Most different components in one recipe
+    pub max_fan_in: usize,
+    /// Most raw resources that go into one point, with the recipes buildings start with
+    pub max_raw_demand: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    pub const ALL: [Difficulty; 3] = [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Difficulty::Easy => "easy",
+            Difficulty::Normal => "normal",
+            Difficulty::Hard => "hard",
+        }
+    }
+
+    /// The preset the constraints are exactly, if any
+    pub fn of(constraints: &RecipeConstraints) -> Option<Difficulty> {
+        Difficulty::ALL
+            .into_iter()
+            .find(|d| &d.constraints() == constraints)
+    }
+
+    pub fn next(&self) -> Difficulty {
+        match self {
+            Difficulty::Easy => Difficulty::Normal,
+            Difficulty::Normal => Difficulty::Hard,
+            Difficulty::Hard => Difficulty::Easy,
+        }
+    }
+
+    pub fn constraints(&self) -> RecipeConstraints {
+        match self {
+            Difficulty::Easy => RecipeConstraints {
+                min_items: 4,
+                max_items: 6,
+                raw_resources: 2,
+                max_depth: 2,
+                max_fan_in: 2,
+                max_raw_demand: 8,
+            },
+            Difficulty::Normal => RecipeConstraints {
+                min_items: MIN_ITEMS,
+                max_items: MAX_ITEMS,
+                raw_resources: MAX_SPAWNABLE_ITEMS,
+                max_depth: 4,
+                max_fan_in: 3,
+                max_raw_demand: 40,
+            },
+            Difficulty::Hard => RecipeConstraints {
+                min_items: 9,
+                max_items: 14,
+                raw_resources: 4,
+                max_depth: 6,
+                max_fan_in: 4,
+                max_raw_demand: 120,
+            },
+        }
+    }
+}
+
+impl std::str::FromStr for Difficulty {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "easy" => Ok(Difficulty::Easy),
+            "normal" => Ok(Difficulty::Normal),
+            "hard" => Ok(Difficulty::Hard),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Default for RecipeConstraints {
+    fn default() -> Self {
+        Difficulty::Normal.constraints()
+    }
+}
+
+impl RecipeConstraints {
+    /// Describes what's wrong, if these can't ever be met
+    pub fn validate(&self) -> Result<(), String> {
+        if self.min_items > self.max_items {
+            return Err("the minimum item count is above the maximum".to_string());
+        }
+        if self.raw_resources == 0 || self.max_depth == 0 || self.max_fan_in == 0 {
+            return Err("raw resources, depth and fan-in have to be at least 1".to_string());
+        }
+        if self.raw_resources >= self.min_items {
+            return Err("there have to be more items than raw resources".to_string());
+        }
+        if self.max_raw_demand < self.raw_resources {
+            return Err("every raw resource needs to go into a point at least once".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Tries a few recipe trees until one meets the constraints. If none does, the closest one is
+/// used with a warning
+fn generate_recipes(constraints: &RecipeConstraints, rng: &mut StdRng) -> Vec<Item> {
+    let mut closest: Option<((usize, usize), Vec<Item>)> = None;
+    for _ in 0..MAX_RECIPE_ATTEMPTS {
+        let items = generate_recipe_tree(constraints, rng);
+        let raw_count = items.iter().filter(|i| i.is_spawnable()).count();
+        let demand = raw_demand(&items);
+        let miss = (
+            raw_count.abs_diff(constraints.raw_resources),
+            demand.saturating_sub(constraints.max_raw_demand),
+        );
+        if miss == (0, 0) {
+            return items;
+        }
+        if closest.as_ref().is_none_or(|(closest, _)| miss < *closest) {
+            closest = Some((miss, items));
+        }
+    }
+
+    let (_, items) = closest.unwrap();
+    eprintln!(
+        "Couldn't meet the recipe constraints, using recipes with {} raw resources and {} raw \
+         resources per point",
+        items.iter().filter(|i| i.is_spawnable()).count(),
+        raw_demand(&items),
+    );
+    items
 }
 
-fn generate_recipes(rng: &mut StdRng) -> Vec<Item> {
-    let item_count = rng.gen_range(MIN_ITEMS..=MAX_ITEMS);
+fn generate_recipe_tree(constraints: &RecipeConstraints, rng: &mut StdRng) -> Vec<Item> {
+    let item_count = rng.gen_range(constraints.min_items..=constraints.max_items);
     // First x items are spawnable (no components), components always smaller idx than parent
     // Last item is "point"
     let starting_hue: f32 = rng.gen();
@@ -29,24 +195,32 @@ fn generate_recipes(rng: &mut StdRng) -> Vec<Item> {
     let mut items: Vec<Item> = (0..item_count)
         .map(|i| Item {
             id: i,
-            color: starting_color
-                .shift_hue((360.0 / item_count as f32) * (i as f32))
-                .into(),
-            components: BTreeMap::new(),
-            time: rng.gen_range(MIN_ITEM_TIME..=MAX_ITEM_TIME),
+            name: String::new(),
+            label: String::new(),
+            shape: Shape::default(),
+            color: Srgb::default(),
+            recipes: vec![Recipe {
+                components: BTreeMap::new(),
+                time: rng.gen_range(MIN_ITEM_TIME..=MAX_ITEM_TIME),
+            }],
         })
         .collect();
 
-    for item_idx in MAX_SPAWNABLE_ITEMS..item_count {
-        let component_count = rng.gen_range(1..=MAX_COMPONENTS);
+    for item_idx in constraints.raw_resources..item_count {
         let (before, after) = items.split_at_mut(item_idx);
+        // Anything deeper would make this item too deep
+        let before: Vec<&Item> = before
+            .iter()
+            .filter(|i| depth(i) < constraints.max_depth)
+            .collect();
         let item = after.first_mut().unwrap();
-        for _ in 0..component_count {
-            let component_idx = rng.gen_range(0..before.len());
-            *item
-                .components
-                .entry(before[component_idx].clone())
-                .or_default() += 1;
+        item.recipes[0].components = generate_components(&before, constraints.max_fan_in, rng);
+
+        while item.recipes.len() < MAX_RECIPES && rng.gen_bool(ALTERNATIVE_RECIPE_CHANCE) {
+            item.recipes.push(Recipe {
+                components: generate_components(&before, constraints.max_fan_in, rng),
+                time: rng.gen_range(MIN_ITEM_TIME..=MAX_ITEM_TIME),
+            });
         }
     }
 
@@ -58,113 +232,244 @@ fn generate_recipes(rng: &mut StdRng) -> Vec<Item> {
     let point = point.id;
     items.retain(|i| i.id == point || needed_for_point.contains(&i.id));
 
+    number(&mut items, starting_color);
     items
 }
 
+/// Gives the items that are left their ids and looks by position, so that palettes, which pick
+/// colors by id, and shapes both stay evenly spread
+fn number(items: &mut [Item], starting_color: Hsv) {
+    let count = items.len();
+    let shapes = Shape::ALL.len();
+    let old_ids: Vec<usize> = items.iter().map(|i| i.id).collect();
+    for idx in 0..count {
+        let (before, after) = items.split_at_mut(idx);
+        let item = &mut after[0];
+        item.id = idx;
+        item.name = format!("Item {idx}");
+        item.label = idx.to_string();
+        // Told apart by shape as well as by color. Palettes repeat every `shapes` items, so each
+        // round of them is shifted to a new shape to keep every pair different
+        item.shape = Shape::ALL[(idx + idx / shapes) % shapes];
+        item.color = starting_color
+            .shift_hue((360.0 / count as f32) * (idx as f32))
+            .into();
+        // Components are copies, and always come before the items made from them
+        for recipe in &mut item.recipes {
+            recipe.components = std::mem::take(&mut recipe.components)
+                .into_iter()
+                .map(|(component, amount)| {
+                    let position = old_ids.iter().position(|&id| id == component.id).unwrap();
+                    (before[position].clone(), amount)
+                })
+                .collect();
+        }
+    }
+}
+
+fn generate_components(
+    before: &[&Item],
+    max_fan_in: usize,
+    rng: &mut StdRng,
+) -> BTreeMap<Item, usize> {
+    let mut components: BTreeMap<Item, usize> = BTreeMap::new();
+    let component_count = rng.gen_range(1..=MAX_COMPONENTS);
+    for _ in 0..component_count {
+        // Once there are enough different components, only add more of the same
+        let component = if components.len() < max_fan_in {
+            before[rng.gen_range(0..before.len())].clone()
+        } else {
+            let idx = rng.gen_range(0..components.len());
+            components.keys().nth(idx).unwrap().clone()
+        };
+        *components.entry(component).or_default() += 1;
+    }
+    components
+}
+
+/// Crafting steps from the furthest raw resource, through any of the recipes
+fn depth(item: &Item) -> usize {
+    item.recipes
+        .iter()
+        .flat_map(|r| r.components.keys())
+        .map(|component| depth(component) + 1)
+        .max()
+        .unwrap_or(0)
+}
+
+/// Raw resources that go into one of the last item, with the recipes buildings start with
+fn raw_demand(items: &[Item]) -> usize {
+    let choices = cheapest_recipes(items);
+    let mut demand: BTreeMap<usize, usize> = BTreeMap::new();
+    // Components always have a smaller id than their parent, so going in order works
+    for item in items {
+        let recipe = &item.recipes[choices[&item.id]];
+        let item_demand = if item.is_spawnable() {
+            1
+        } else {
+            recipe
+                .components
+                .iter()
+                .map(|(component, count)| demand[&component.id] * count)
+                .sum()
+        };
+        demand.insert(item.id, item_demand);
+    }
+    demand[&items.last().unwrap().id]
+}
+
+/// What the world's recipes look like, printed at startup
+pub fn recipe_summary(items: &[Item]) -> String {
+    let point = items.last().unwrap();
+    let choices = cheapest_recipes(items);
+    let raw_count = items.iter().filter(|i| i.is_spawnable()).count();
+    let mut summary = format!(
+        "{} items, {raw_count} raw resources, depth {}, {} raw resources per point\n",
+        items.len(),
+        depth(point),
+        raw_demand(items),
+    );
+    for item in items.iter().filter(|i| !i.is_spawnable()) {
+        let recipes: Vec<String> = item
+            .recipes
+            .iter()
+            .enumerate()
+            .map(|(idx, recipe)| {
+                let components: Vec<String> = recipe
+                    .components
+                    .iter()
+                    .map(|(component, count)| format!("{count}x {}", component.name))
+                    .collect();
+                let default = if choices[&item.id] == idx { "*" } else { "" };
+                format!("{} ({:.1}s){default}", components.join(" + "), recipe.time)
+            })
+            .collect();
+        let point = if item == point { " (point)" } else { "" };
+        summary += &format!("  {}{point} <- {}\n", item.name, recipes.join(" | "));
+    }
+    summary
+}
+
+/// Every item that can end up being used to make `item`, through any of the recipes
 fn recursive_needed_for(item: &Item) -> Vec<&Item> {
-    item.components
-        .keys()
+    item.recipes
+        .iter()
+        .flat_map(|r| r.components.keys())
         .flat_map(|k| Some(k).into_iter().chain(recursive_needed_for(k)))
         .collect()
 }
 
-fn generate_grid_items(items: &[Item], rng: &mut StdRng) -> GridItems {
-    let mut grid_items = GridItems::new();
+/// Generates the layout and makes sure every building can be supplied and delivered from
+fn generate_layout(
+    layout: Layout,
+    buildings: Vec<Building>,
+    rng: &mut StdRng,
+) -> Result<GridItems, LayoutError> {
+    let grid_items = layout.generator().generate(buildings, rng)?;
+    layout::check_reachable(&grid_items)?;
+    Ok(grid_items)
+}
 
-    let buildings = buildings_for(items.last().unwrap());
-    let grid_size = buildings.len() as isize / 2;
+/// Covers every building with poles and places enough generators to run them all
+fn generate_power(grid_items: &mut GridItems) {
+    let buildings: Vec<Position> = grid_items
+        .iter()
+        .filter(|(_, grid_item)| matches!(grid_item, GridItem::Building(..)))
+        .map(|(pos, _)| *pos)
+        .collect();
 
-    for x in (-grid_size)..grid_size {
-        grid_items.insert(Position(x, 0), GridItem::Rail(Orientation::Horizontal));
-    }
-    for y in (-grid_size)..grid_size {
-        grid_items.insert(Position(0, y), GridItem::Rail(Orientation::Vertical));
+    let mut covered = BTreeSet::new();
+    for building in buildings {
+        if covered.contains(&building) {
+            continue;
+        }
+        if let Some(pole) = free_positions_near(building, grid_items).first() {
+            grid_items.insert(*pole, GridItem::PowerPole);
+            covered.extend(power::in_range(*pole));
+        }
     }
-    grid_items.insert(
-        Position(0, 0),
-        GridItem::Intersection(IntersectionType::Quad),
-    );
 
-    for b in buildings.into_iter() {
-        loop {
-            let direction: Direction = rng.gen();
-            let connection_distance = rng.gen_range(2..grid_size);
-            let connection_position = direction.to_position() * connection_distance;
-            let offset_direction = if rng.gen_bool(0.5) {
-                direction.left()
-            } else {
-                direction.right()
-            };
-
-            let connection_entry = grid_items
-                .get_mut(&connection_position)
-                .expect("intersection or rail");
-            if matches!(connection_entry, GridItem::Intersection(..)) {
-                // taken, try again
-                continue;
-            }
-
-            *connection_entry = GridItem::Intersection(IntersectionType::Triple(offset_direction));
-            let mut building_position = connection_position;
-            let offset = rng.gen_range(1..connection_distance);
-            for _ in 0..offset {
-                building_position = building_position + offset_direction;
-                grid_items.insert(
-                    building_position,
-                    GridItem::Rail(offset_direction.to_orientation()),
-                );
-            }
-            grid_items.insert(
-                building_position,
-                GridItem::Building(b, offset_direction.opposite()),
-            );
-            break;
-        }
+    let demand = PowerNetwork::calculate(grid_items).demand;
+    let generator_count = (demand / GENERATOR_OUTPUT).ceil() as usize;
+    let mut free: Vec<Position> = covered
+        .into_iter()
+        .filter(|p| !grid_items.contains_key(p))
+        .collect();
+    free.sort_by_key(|p| p.0.abs() + p.1.abs());
+    for position in free.into_iter().take(generator_count) {
+        grid_items.insert(position, GridItem::Generator);
     }
+}
 
-    grid_items
+/// Empty positions a pole placed at `position` would reach, closest first
+fn free_positions_near(position: Position, grid_items: &GridItems) -> Vec<Position> {
+    let mut free: Vec<Position> = power::in_range(position)
+        .filter(|p| !grid_items.contains_key(p))
+        .collect();
+    free.sort_by_key(|p| (p.0 - position.0).pow(2) + (p.1 - position.1).pow(2));
+    free
 }
 
-fn buildings_for(root_item: &Item) -> Vec<Building> {
-    let counts: BTreeMap<_, _> = building_counts_for(root_item)
+fn buildings_for(items: &[Item]) -> Vec<Building> {
+    let root_item = items.last().unwrap();
+    let choices = cheapest_recipes(items);
+    let mut counts: BTreeMap<_, _> = building_counts_for(root_item, &choices)
         .into_iter()
         .map(|(k, v)| (k, v.ceil() as usize))
         .collect();
+    // Items only used by alternative recipes still get a building so that switching to them works
+    for item in items {
+        counts.entry(item).or_insert(1);
+    }
+
     counts
         .into_iter()
         .flat_map(|(item, count)| {
-            (0..count).map(move |_| {
-                if item.components.is_empty() {
-                    Building::Spawner {
-                        item: item.clone(),
-                        timer: RefCell::new(0.0),
-                        spawn_timer: RefCell::new(ITEM_SPAWN_ANIMATION_TIME),
-                    }
-                } else if item == root_item {
-                    // points' buildings are actually submitters
-                    Building::Submitter {
-                        item: item.clone(),
-                        contents: RefCell::new(BTreeMap::new()),
-                    }
-                } else {
-                    Building::Crafter {
-                        item: item.clone(),
-                        contents: RefCell::new(BTreeMap::new()),
-                        timer: RefCell::new(0.0),
-                        spawn_timer: RefCell::new(ITEM_SPAWN_ANIMATION_TIME),
-                    }
-                }
-            })
+            let recipe = choices[&item.id];
+            (0..count).map(move |_| Building::new(item, item == root_item, recipe))
         })
         .collect()
 }
 
-fn building_counts_for(item: &Item) -> BTreeMap<&Item, f64> {
+/// Item id --> index of the recipe that needs the least total building time to sustain
+fn cheapest_recipes(items: &[Item]) -> BTreeMap<usize, usize> {
+    // Components always have a smaller id than their parent, so going in order works
+    let mut costs: BTreeMap<usize, f64> = BTreeMap::new();
+    let mut choices = BTreeMap::new();
+
+    for item in items {
+        let (choice, cost) = item
+            .recipes
+            .iter()
+            .map(|recipe| {
+                recipe.time
+                    + recipe
+                        .components
+                        .iter()
+                        .map(|(component, &count)| costs[&component.id] * count as f64)
+                        .sum::<f64>()
+            })
+            .enumerate()
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .unwrap();
+        costs.insert(item.id, cost);
+        choices.insert(item.id, choice);
+    }
+
+    choices
+}
+
+/// `choices` maps item id to the recipe index used for it, see `cheapest_recipes`
+fn building_counts_for<'a>(
+    item: &'a Item,
+    choices: &BTreeMap<usize, usize>,
+) -> BTreeMap<&'a Item, f64> {
+    let recipe = &item.recipes[choices[&item.id]];
     let mut buildings = BTreeMap::new();
-    buildings.insert(item, item.time);
+    buildings.insert(item, recipe.time);
 
-    for (component, count) in &item.components {
-        let component_buildings = building_counts_for(component);
+    for (component, count) in &recipe.components {
+        let component_buildings = building_counts_for(component, choices);
         for (subcomponent, subcomponent_count) in &component_buildings {
             *buildings.entry(subcomponent).or_default() += subcomponent_count * (*count as f64);
         }
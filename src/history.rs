@@ -0,0 +1,93 @@
+use crate::model::*;
+
+/// A single tile change, with enough information to undo and redo it
+#[derive(Debug, Clone)]
+pub struct Edit {
+    pub position: Position,
+    pub change: Change,
+    /// What the edit added to the balance, negative if it cost something
+    pub balance_change: isize,
+}
+
+#[derive(Debug, Clone)]
+#[allow(clippy::large_enum_variant)]
+pub enum Change {
+    /// What's on the tile is swapped out. Whichever side isn't on the grid is kept the way it was
+    /// when it was taken off, so that putting it back doesn't undo what happened to it meanwhile
+    Replace {
+        before: Option<GridItem>,
+        after: Option<GridItem>,
+    },
+    /// Turned a quarter clockwise, in place
+    Rotate,
+    /// The building bought its next tier
+    Upgrade,
+}
+
+impl Change {
+    /// Puts `after` on the tile, what was there is filled in once the edit is made
+    pub fn replacing(after: Option<GridItem>) -> Change {
+        Change::Replace {
+            before: None,
+            after,
+        }
+    }
+}
+
+/// Undo and redo stacks of edits. Edits made between `begin_batch` and `end_batch` are undone
+/// together, for example a whole dragged line of rails
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    undo: Vec<Vec<Edit>>,
+    redo: Vec<Vec<Edit>>,
+    batching: bool,
+}
+
+impl History {
+    pub fn record(&mut self, edit: Edit) {
+        self.redo.clear();
+        match self.undo.last_mut() {
+            Some(batch) if self.batching => batch.push(edit),
+            _ => self.undo.push(vec![edit]),
+        }
+    }
+
+    pub fn begin_batch(&mut self) {
+        self.end_batch();
+        self.undo.push(vec![]);
+        self.batching = true;
+    }
+
+    pub fn end_batch(&mut self) {
+        if self.batching && self.undo.last().is_some_and(Vec::is_empty) {
+            self.undo.pop();
+        }
+        self.batching = false;
+    }
+
+    /// Takes the next batch to undo. Once reverted it goes on the redo stack, otherwise back.
+    /// None while a batch is open, the edits still coming belong to it
+    pub fn take_undo(&mut self) -> Option<Vec<Edit>> {
+        if self.batching {
+            return None;
+        }
+        self.undo.pop()
+    }
+
+    /// Takes the next batch to redo. Once reapplied it goes on the undo stack, otherwise back.
+    /// None while a batch is open
+    pub fn take_redo(&mut self) -> Option<Vec<Edit>> {
+        if self.batching {
+            return None;
+        }
+        self.redo.pop()
+    }
+
+    pub fn push_undo(&mut self, batch: Vec<Edit>) {
+        self.undo.push(batch);
+    }
+
+    pub fn push_redo(&mut self, batch: Vec<Edit>) {
+        self.redo.push(batch);
+    }
+}
@@ -0,0 +1,450 @@
+use std::collections::BTreeMap;
+
+use nannou::{prelude::*, winit::event::ModifiersState};
+
+/// Something the player can do with a key or mouse button
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    Pause,
+    SpeedUp,
+    SlowDown,
+    ZoomIn,
+    ZoomOut,
+    ResetCamera,
+    /// Held while dragging the view around
+    Pan,
+    /// Uses the current tool, held to keep using it
+    Build,
+    Upgrade,
+    Undo,
+    Redo,
+    Copy,
+    SaveBlueprint,
+    ExportBlueprint,
+    PreviousBlueprint,
+    NextBlueprint,
+    /// The tool, or the clipboard when pasting
+    Rotate,
+    Mirror,
+    NextItem,
+    PowerOverlay,
+    Minimap,
+    TrafficOverlay,
+    /// Production per item against what the buildings could make at their tiers
+    Statistics,
+    /// Cycles how far back the traffic heatmap counts
+    TrafficWindow,
+    ResetTraffic,
+    SeekBack,
+    SeekForward,
+    Palette,
+    Labels,
+    Theme,
+    Settings,
+    Help,
+    Screenshot,
+    /// Starts or stops saving every frame
+    RecordFrames,
+    /// Pauses and opens the pause menu
+    Menu,
+    InspectTool,
+    RailTool,
+    IntersectionTool,
+    BuildingTool,
+    GeneratorTool,
+    PowerPoleTool,
+    RotateTool,
+    SelectTool,
+    PasteTool,
+    RemoveTool,
+}
+
+/// A key or button, with the modifiers that have to be held with it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Binding {
+    pub input: Input,
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Input {
+    Key(Key),
+    Mouse(MouseButton),
+    WheelUp,
+    WheelDown,
+}
+
+/// What every command is bound to, the defaults with the settings' overrides applied
+#[derive(Debug, Clone)]
+pub struct Bindings(Vec<(Command, Binding)>);
+
+/// Keys that can be named in the settings, by their `Debug` names
+const KEYS: [Key; 77] = [
+    Key::Key1,
+    Key::Key2,
+    Key::Key3,
+    Key::Key4,
+    Key::Key5,
+    Key::Key6,
+    Key::Key7,
+    Key::Key8,
+    Key::Key9,
+    Key::Key0,
+    Key::A,
+    Key::B,
+    Key::C,
+    Key::D,
+    Key::E,
+    Key::F,
+    Key::G,
+    Key::H,
+    Key::I,
+    Key::J,
+    Key::K,
+    Key::L,
+    Key::M,
+    Key::N,
+    Key::O,
+    Key::P,
+    Key::Q,
+    Key::R,
+    Key::S,
+    Key::T,
+    Key::U,
+    Key::V,
+    Key::W,
+    Key::X,
+    Key::Y,
+    Key::Z,
+    Key::F1,
+    Key::F2,
+    Key::F3,
+    Key::F4,
+    Key::F5,
+    Key::F6,
+    Key::F7,
+    Key::F8,
+    Key::F9,
+    Key::F10,
+    Key::F11,
+    Key::F12,
+    Key::Escape,
+    Key::Tab,
+    Key::Space,
+    Key::Return,
+    Key::Back,
+    Key::Insert,
+    Key::Delete,
+    Key::Home,
+    Key::End,
+    Key::PageUp,
+    Key::PageDown,
+    Key::Left,
+    Key::Right,
+    Key::Up,
+    Key::Down,
+    Key::Equals,
+    Key::Plus,
+    Key::Minus,
+    Key::LBracket,
+    Key::RBracket,
+    Key::Comma,
+    Key::Period,
+    Key::Slash,
+    Key::Backslash,
+    Key::Semicolon,
+    Key::Apostrophe,
+    Key::Grave,
+    Key::NumpadAdd,
+    Key::NumpadSubtract,
+];
+
+impl Command {
+    pub const ALL: [Command; 45] = [
+        Command::Pause,
+        Command::SpeedUp,
+        Command::SlowDown,
+        Command::ZoomIn,
+        Command::ZoomOut,
+        Command::ResetCamera,
+        Command::Pan,
+        Command::Build,
+        Command::Upgrade,
+        Command::Undo,
+        Command::Redo,
+        Command::Copy,
+        Command::SaveBlueprint,
+        Command::ExportBlueprint,
+        Command::PreviousBlueprint,
+        Command::NextBlueprint,
+        Command::Rotate,
+        Command::Mirror,
+        Command::NextItem,
+        Command::PowerOverlay,
+        Command::Minimap,
+        Command::TrafficOverlay,
+        Command::Statistics,
+        Command::TrafficWindow,
+        Command::ResetTraffic,
+        Command::SeekBack,
+        Command::SeekForward,
+        Command::Palette,
+        Command::Labels,
+        Command::Theme,
+        Command::Settings,
+        Command::Help,
+        Command::Screenshot,
+        Command::RecordFrames,
+        Command::Menu,
+        Command::InspectTool,
+        Command::RailTool,
+        Command::IntersectionTool,
+        Command::BuildingTool,
+        Command::GeneratorTool,
+        Command::PowerPoleTool,
+        Command::RotateTool,
+        Command::SelectTool,
+        Command::PasteTool,
+        Command::RemoveTool,
+    ];
+
+    /// Used in the settings' `key_bindings`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Command::Pause => "pause",
+            Command::SpeedUp => "speed_up",
+            Command::SlowDown => "slow_down",
+            Command::ZoomIn => "zoom_in",
+            Command::ZoomOut => "zoom_out",
+            Command::ResetCamera => "reset_camera",
+            Command::Pan => "pan",
+            Command::Build => "build",
+            Command::Upgrade => "upgrade",
+            Command::Undo => "undo",
+            Command::Redo => "redo",
+            Command::Copy => "copy",
+            Command::SaveBlueprint => "save_blueprint",
+            Command::ExportBlueprint => "export_blueprint",
+            Command::PreviousBlueprint => "previous_blueprint",
+            Command::NextBlueprint => "next_blueprint",
+            Command::Rotate => "rotate",
+            Command::Mirror => "mirror",
+            Command::NextItem => "next_item",
+            Command::PowerOverlay => "power_overlay",
+            Command::Minimap => "minimap",
+            Command::TrafficOverlay => "traffic_overlay",
+            Command::Statistics => "statistics",
+            Command::TrafficWindow => "traffic_window",
+            Command::ResetTraffic => "reset_traffic",
+            Command::SeekBack => "seek_back",
+            Command::SeekForward => "seek_forward",
+            Command::Palette => "palette",
+            Command::Labels => "labels",
+            Command::Theme => "theme",
+            Command::Settings => "settings",
+            Command::Help => "help",
+            Command::Screenshot => "screenshot",
+            Command::RecordFrames => "record_frames",
+            Command::Menu => "menu",
+            Command::InspectTool => "inspect_tool",
+            Command::RailTool => "rail_tool",
+            Command::IntersectionTool => "intersection_tool",
+            Command::BuildingTool => "building_tool",
+            Command::GeneratorTool => "generator_tool",
+            Command::PowerPoleTool => "power_pole_tool",
+            Command::RotateTool => "rotate_tool",
+            Command::SelectTool => "select_tool",
+            Command::PasteTool => "paste_tool",
+            Command::RemoveTool => "remove_tool",
+        }
+    }
+
+    fn default_bindings(&self) -> &'static [&'static str] {
+        match self {
+            Command::Pause => &["Space"],
+            Command::SpeedUp => &["Equals", "Plus"],
+            Command::SlowDown => &["Minus"],
+            Command::ZoomIn => &["WheelUp", "Ctrl+Equals"],
+            Command::ZoomOut => &["WheelDown", "Ctrl+Minus"],
+            Command::ResetCamera => &["Home"],
+            Command::Pan => &["MouseMiddle"],
+            Command::Build => &["MouseLeft"],
+            Command::Upgrade => &["MouseRight"],
+            Command::Undo => &["Ctrl+Z"],
+            Command::Redo => &["Ctrl+Shift+Z", "Ctrl+Y"],
+            Command::Copy => &["Ctrl+C"],
+            Command::SaveBlueprint => &["Ctrl+S"],
+            Command::ExportBlueprint => &["Ctrl+E"],
+            Command::PreviousBlueprint => &["LBracket"],
+            Command::NextBlueprint => &["RBracket"],
+            Command::Rotate => &["R"],
+            Command::Mirror => &["M"],
+            Command::NextItem => &["Tab"],
+            Command::PowerOverlay => &["P"],
+            Command::Minimap => &["N"],
+            Command::TrafficOverlay => &["G"],
+            Command::Statistics => &["S"],
+            Command::TrafficWindow => &["Shift+G"],
+            Command::ResetTraffic => &["Ctrl+G"],
+            Command::SeekBack => &["Left"],
+            Command::SeekForward => &["Right"],
+            Command::Palette => &["C"],
+            Command::Labels => &["L"],
+            Command::Theme => &["T"],
+            Command::Settings => &["O"],
+            Command::Help => &["H", "F1"],
+            Command::Screenshot => &["F12"],
+            Command::RecordFrames => &["Shift+F12"],
+            Command::Menu => &["Escape"],
+            Command::InspectTool => &["Key1"],
+            Command::RailTool => &["Key2"],
+            Command::IntersectionTool => &["Key3"],
+            Command::BuildingTool => &["Key4"],
+            Command::GeneratorTool => &["Key5"],
+            Command::PowerPoleTool => &["Key6"],
+            Command::RotateTool => &["Key7"],
+            Command::SelectTool => &["Key8"],
+            Command::PasteTool => &["Key9"],
+            Command::RemoveTool => &["Key0", "Delete"],
+        }
+    }
+}
+
+impl std::str::FromStr for Command {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Command::ALL.into_iter().find(|c| c.name() == s).ok_or(())
+    }
+}
+
+impl Binding {
+    /// Ctrl and Alt have to match exactly. Shift is only checked when the binding asks for it,
+    /// since some layouts need it to type keys like `Plus`
+    fn matches(&self, input: Input, mods: ModifiersState) -> bool {
+        self.input == input
+            && self.ctrl == mods.ctrl()
+            && self.alt == mods.alt()
+            && (!self.shift || mods.shift())
+    }
+}
+
+impl std::fmt::Display for Binding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (held, name) in [
+            (self.ctrl, "Ctrl"),
+            (self.shift, "Shift"),
+            (self.alt, "Alt"),
+        ] {
+            if held {
+                write!(f, "{name}+")?;
+            }
+        }
+        match self.input {
+            Input::Key(key) => write!(f, "{key:?}"),
+            Input::Mouse(MouseButton::Other(button)) => write!(f, "Mouse{button}"),
+            Input::Mouse(button) => write!(f, "Mouse{button:?}"),
+            Input::WheelUp => write!(f, "WheelUp"),
+            Input::WheelDown => write!(f, "WheelDown"),
+        }
+    }
+}
+
+/// Modifiers joined with `+` before a key name, mouse button or wheel direction, e.g.
+/// `Ctrl+Shift+Z`, `MouseMiddle` or `WheelUp`
+impl std::str::FromStr for Binding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts: Vec<&str> = s.split('+').map(str::trim).collect();
+        // `Ctrl++` is Ctrl with the plus key
+        if s.ends_with("++") {
+            parts.truncate(parts.len() - 2);
+            parts.push("Plus");
+        }
+        let name = parts.pop().unwrap_or_default();
+        let mut binding = Binding {
+            input: parse_input(name).ok_or_else(|| format!("unknown key {name}"))?,
+            ctrl: false,
+            shift: false,
+            alt: false,
+        };
+        for modifier in parts {
+            match modifier.to_lowercase().as_str() {
+                "ctrl" => binding.ctrl = true,
+                "shift" => binding.shift = true,
+                "alt" => binding.alt = true,
+                _ => return Err(format!("unknown modifier {modifier}")),
+            }
+        }
+        Ok(binding)
+    }
+}
+
+fn parse_input(name: &str) -> Option<Input> {
+    let input = match name {
+        "MouseLeft" => Input::Mouse(MouseButton::Left),
+        "MouseRight" => Input::Mouse(MouseButton::Right),
+        "MouseMiddle" => Input::Mouse(MouseButton::Middle),
+        "WheelUp" => Input::WheelUp,
+        "WheelDown" => Input::WheelDown,
+        _ => match name.strip_prefix("Mouse").and_then(|n| n.parse().ok()) {
+            Some(button) => Input::Mouse(MouseButton::Other(button)),
+            None => Input::Key(KEYS.into_iter().find(|k| format!("{k:?}") == name)?),
+        },
+    };
+    Some(input)
+}
+
+impl Bindings {
+    /// The defaults, with every command named in `overrides` bound to its comma separated
+    /// bindings instead. Invalid entries are reported and skipped
+    pub fn new(overrides: &BTreeMap<String, String>) -> Bindings {
+        let mut bindings: Vec<(Command, Binding)> = Command::ALL
+            .into_iter()
+            .flat_map(|c| {
+                c.default_bindings()
+                    .iter()
+                    .map(move |b| (c, b.parse().expect("default bindings are valid")))
+            })
+            .collect();
+
+        for (name, value) in overrides {
+            let Ok(command) = name.parse::<Command>() else {
+                eprintln!("Ignoring key binding for unknown action {name}");
+                continue;
+            };
+            bindings.retain(|(c, _)| *c != command);
+            for binding in value.split(',').filter(|b| !b.trim().is_empty()) {
+                match binding.trim().parse() {
+                    Ok(binding) => bindings.push((command, binding)),
+                    Err(e) => eprintln!("Ignoring key binding {binding} for {name}: {e}"),
+                }
+            }
+        }
+        Bindings(bindings)
+    }
+
+    /// The command bound to the input, preferring bindings that ask for Shift when it's held
+    pub fn command(&self, input: Input, mods: ModifiersState) -> Option<Command> {
+        self.0
+            .iter()
+            .filter(|(_, b)| b.matches(input, mods))
+            .min_by_key(|(_, b)| !b.shift)
+            .map(|(c, _)| *c)
+    }
+
+    /// Whether the input is bound to the command, whatever the modifiers
+    pub fn is_bound(&self, command: Command, input: Input) -> bool {
+        self.of(command).any(|b| b.input == input)
+    }
+
+    pub fn of(&self, command: Command) -> impl Iterator<Item = &Binding> {
+        self.0
+            .iter()
+            .filter(move |(c, _)| *c == command)
+            .map(|(_, b)| b)
+    }
+}
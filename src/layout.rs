@@ -0,0 +1,490 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use rand::{rngs::StdRng, seq::SliceRandom, Rng};
+use serde::{Deserialize, Serialize};
+
+use crate::{constants::*, model::*, train::calculate_path};
+
+/// Lays out the rails of a world and places its buildings on them
+pub trait LayoutGenerator {
+    /// Every building ends up connected to the same network, so they can all reach each other
+    fn generate(
+        &self,
+        buildings: Vec<Building>,
+        rng: &mut StdRng,
+    ) -> Result<GridItems, LayoutError>;
+}
+
+/// Why a layout couldn't be generated
+#[derive(Debug, Clone)]
+pub enum LayoutError {
+    /// There are more buildings than places to put them
+    NoRoom { buildings: usize },
+    /// Nothing that makes the item can get it to the building at the position
+    Unreachable { position: Position, item: usize },
+    /// Nothing at the position can use what the building there makes
+    Unused { position: Position, item: usize },
+}
+
+/// Which `LayoutGenerator` to use, picked on the command line
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Layout {
+    /// Two crossing main lines with a spur to every building
+    #[default]
+    Plus,
+    /// Streets around small blocks
+    Grid,
+    /// One loop with buildings inside and outside
+    Ring,
+    /// A long line with branches off both sides
+    Spine,
+    /// Small loops hanging off a main line, each with the buildings of a few items
+    Districts,
+}
+
+impl Layout {
+    pub fn next(&self) -> Layout {
+        match self {
+            Layout::Plus => Layout::Grid,
+            Layout::Grid => Layout::Ring,
+            Layout::Ring => Layout::Spine,
+            Layout::Spine => Layout::Districts,
+            Layout::Districts => Layout::Plus,
+        }
+    }
+
+    pub fn generator(&self) -> Box<dyn LayoutGenerator> {
+        match self {
+            Layout::Plus => Box::new(Plus),
+            Layout::Grid => Box::new(Manhattan),
+            Layout::Ring => Box::new(Ring),
+            Layout::Spine => Box::new(Spine),
+            Layout::Districts => Box::new(Districts),
+        }
+    }
+}
+
+impl std::fmt::Display for Layout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Layout::Plus => "plus",
+            Layout::Grid => "grid",
+            Layout::Ring => "ring",
+            Layout::Spine => "spine",
+            Layout::Districts => "districts",
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl std::fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LayoutError::NoRoom { buildings } => write!(f, "no room for {buildings} buildings"),
+            LayoutError::Unreachable {
+                position: Position(x, y),
+                item,
+            } => write!(f, "building at {x},{y} can't be reached with item {item}"),
+            LayoutError::Unused {
+                position: Position(x, y),
+                item,
+            } => write!(f, "building at {x},{y} can't deliver item {item} anywhere"),
+        }
+    }
+}
+
+impl std::str::FromStr for Layout {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "plus" => Ok(Layout::Plus),
+            "grid" => Ok(Layout::Grid),
+            "ring" => Ok(Layout::Ring),
+            "spine" => Ok(Layout::Spine),
+            "districts" => Ok(Layout::Districts),
+            _ => Err(()),
+        }
+    }
+}
+
+pub struct Plus;
+pub struct Manhattan;
+pub struct Ring;
+pub struct Spine;
+pub struct Districts;
+
+impl LayoutGenerator for Plus {
+    fn generate(
+        &self,
+        buildings: Vec<Building>,
+        rng: &mut StdRng,
+    ) -> Result<GridItems, LayoutError> {
+        let mut network = Network::default();
+        // Small worlds get longer arms so that every spur has room
+        let count = buildings.len() as isize;
+        let grid_size = (count / 2).max(count / 4 + MIN_PLUS_SIZE);
+        // Every spur needs its own tile on the main lines, and the first tile out from the
+        // middle is too close to branch off
+        let connections = 4 * (grid_size - 2) as usize;
+        if buildings.len() > connections {
+            return Err(LayoutError::NoRoom {
+                buildings: buildings.len(),
+            });
+        }
+
+        network.line(Position(-grid_size, 0), Position(grid_size - 1, 0));
+        network.line(Position(0, -grid_size), Position(0, grid_size - 1));
+
+        for b in buildings.into_iter() {
+            let Some((connection, spur, building_position, building_direction)) =
+                (0..MAX_PLACEMENT_ATTEMPTS).find_map(|_| Plus::spur(&network, grid_size, rng))
+            else {
+                return Err(LayoutError::NoRoom {
+                    buildings: network.buildings.len() + 1,
+                });
+            };
+
+            network.line(connection, *spur.last().unwrap_or(&connection));
+            network.place(b, building_position, building_direction);
+        }
+
+        Ok(network.into_grid_items())
+    }
+}
+
+impl Plus {
+    /// Picks a random free spot along the main lines and a straight or bent spur from it.
+    /// Returns where it branches off, the spur's rails and where the building goes, None if it
+    /// would cross something already there
+    fn spur(
+        network: &Network,
+        grid_size: isize,
+        rng: &mut StdRng,
+    ) -> Option<(Position, Vec<Position>, Position, Direction)> {
+        let direction: Direction = rng.gen();
+        let connection_distance = rng.gen_range(2..grid_size);
+        let connection_position = direction.to_position() * connection_distance;
+        let offset_direction = if rng.gen_bool(0.5) {
+            direction.left()
+        } else {
+            direction.right()
+        };
+
+        if network.rails[&connection_position].len() > 2 {
+            // taken
+            return None;
+        }
+
+        let offset = rng.gen_range(1..connection_distance);
+        let mut spur: Vec<Position> = (1..=offset)
+            .map(|i| connection_position + offset_direction.to_position() * i)
+            .collect();
+        let end = *spur.last().unwrap();
+        // Sometimes bend the spur to run alongside the main line
+        let bend = (offset > 1 && rng.gen_bool(BEND_CHANCE)).then(|| {
+            if rng.gen_bool(0.5) {
+                direction
+            } else {
+                direction.opposite()
+            }
+        });
+        let (building_position, building_direction) = match bend {
+            Some(bend) if network.is_free(end + bend) => (end + bend, bend.opposite()),
+            _ => {
+                spur.pop();
+                (end, offset_direction.opposite())
+            }
+        };
+
+        let free = spur
+            .iter()
+            .chain([&building_position])
+            .all(|p| network.is_free(*p));
+        free.then_some((
+            connection_position,
+            spur,
+            building_position,
+            building_direction,
+        ))
+    }
+}
+
+impl LayoutGenerator for Manhattan {
+    fn generate(
+        &self,
+        buildings: Vec<Building>,
+        rng: &mut StdRng,
+    ) -> Result<GridItems, LayoutError> {
+        let mut network = Network::default();
+        // Every tile inside a block is next to a street
+        let blocks = (buildings.len() as f64 / 4.0).sqrt().ceil().max(1.0) as isize;
+        let (blocks_x, blocks_y) = (blocks + rng.gen_range(0..=1), blocks);
+        let (width, height) = (blocks_x * BLOCK_SIZE, blocks_y * BLOCK_SIZE);
+
+        for x in 0..=blocks_x {
+            network.line(
+                Position(x * BLOCK_SIZE, 0),
+                Position(x * BLOCK_SIZE, height),
+            );
+        }
+        for y in 0..=blocks_y {
+            network.line(Position(0, y * BLOCK_SIZE), Position(width, y * BLOCK_SIZE));
+        }
+
+        let sites = network.sites();
+        network.place_randomly(buildings, sites, rng)?;
+        Ok(network.into_grid_items())
+    }
+}
+
+impl LayoutGenerator for Ring {
+    fn generate(
+        &self,
+        buildings: Vec<Building>,
+        rng: &mut StdRng,
+    ) -> Result<GridItems, LayoutError> {
+        let mut network = Network::default();
+        // Buildings fit on both sides of the loop
+        let side = buildings.len() as isize / 4 + 2;
+        let (width, height) = (side + rng.gen_range(0..=2), side + rng.gen_range(0..=2));
+
+        network.line(Position(0, 0), Position(width, 0));
+        network.line(Position(width, 0), Position(width, height));
+        network.line(Position(width, height), Position(0, height));
+        network.line(Position(0, height), Position(0, 0));
+
+        let sites = network.sites();
+        network.place_randomly(buildings, sites, rng)?;
+        Ok(network.into_grid_items())
+    }
+}
+
+impl LayoutGenerator for Spine {
+    fn generate(
+        &self,
+        buildings: Vec<Building>,
+        rng: &mut StdRng,
+    ) -> Result<GridItems, LayoutError> {
+        let mut network = Network::default();
+        let mut x = 0;
+
+        // Keep growing until there's more than enough room
+        while network.site_count() < buildings.len() * 2 {
+            if x > 0 {
+                network.line(Position(x - BRANCH_SPACING, 0), Position(x, 0));
+            }
+            for sign in [1, -1] {
+                let length = rng.gen_range(MIN_BRANCH_LENGTH..=MAX_BRANCH_LENGTH);
+                let end = Position(x, length * sign);
+                network.line(Position(x, 0), end);
+                // Twigs off the end of a branch
+                if rng.gen_bool(BEND_CHANCE) {
+                    let twig = if rng.gen_bool(0.5) {
+                        Direction::East
+                    } else {
+                        Direction::West
+                    };
+                    network.connect(end, end + twig);
+                }
+            }
+            x += BRANCH_SPACING;
+        }
+
+        let sites = network.sites();
+        network.place_randomly(buildings, sites, rng)?;
+        Ok(network.into_grid_items())
+    }
+}
+
+impl LayoutGenerator for Districts {
+    fn generate(
+        &self,
+        buildings: Vec<Building>,
+        rng: &mut StdRng,
+    ) -> Result<GridItems, LayoutError> {
+        let mut network = Network::default();
+        // Buildings come sorted by item, so each district gets a few items' worth
+        let districts: Vec<Vec<Building>> = buildings
+            .chunks(DISTRICT_SIZE)
+            .map(<[Building]>::to_vec)
+            .collect();
+
+        let mut loops = vec![];
+        for i in 0..districts.len() {
+            let sign = if i % 2 == 0 { 1 } else { -1 };
+            let x = (i / 2) as isize * DISTRICT_SPACING;
+            let (near, far) = (
+                DISTRICT_DISTANCE * sign,
+                (DISTRICT_DISTANCE + DISTRICT_HEIGHT) * sign,
+            );
+            let corners = [
+                Position(x, near),
+                Position(x + DISTRICT_WIDTH, near),
+                Position(x + DISTRICT_WIDTH, far),
+                Position(x, far),
+            ];
+
+            let before = network.rails.keys().copied().collect::<BTreeSet<_>>();
+            for (from, to) in corners.iter().zip(corners.iter().cycle().skip(1)) {
+                network.line(*from, *to);
+            }
+            let loop_tiles: BTreeSet<Position> = network
+                .rails
+                .keys()
+                .filter(|p| !before.contains(p))
+                .copied()
+                .collect();
+            loops.push(loop_tiles);
+
+            let connector = x + DISTRICT_WIDTH / 2;
+            network.line(Position(connector, 0), Position(connector, near));
+        }
+        let last_x = (districts.len() as isize - 1) / 2 * DISTRICT_SPACING;
+        network.line(Position(0, 0), Position(last_x + DISTRICT_WIDTH, 0));
+
+        for (district, loop_tiles) in districts.into_iter().zip(loops) {
+            let sites = network
+                .sites()
+                .into_iter()
+                .filter(|(position, facing)| loop_tiles.contains(&(*position + *facing)))
+                .collect();
+            network.place_randomly(district, sites, rng)?;
+        }
+        Ok(network.into_grid_items())
+    }
+}
+
+/// Rails being laid out, as the directions each tile connects to, and the buildings on them
+#[derive(Debug, Default)]
+struct Network {
+    rails: BTreeMap<Position, Vec<Direction>>,
+    buildings: GridItems,
+}
+
+impl Network {
+    /// Joins two neighboring tiles
+    fn connect(&mut self, a: Position, b: Position) {
+        let direction = a.direction_towards(b).expect("tiles are neighbors");
+        for (position, direction) in [(a, direction), (b, direction.opposite())] {
+            let connections = self.rails.entry(position).or_default();
+            if !connections.contains(&direction) {
+                connections.push(direction);
+            }
+        }
+    }
+
+    /// Rails from one tile to another, first horizontally then vertically
+    fn line(&mut self, from: Position, to: Position) {
+        let mut current = from;
+        while current != to {
+            let next = if current.0 != to.0 {
+                Position(current.0 + (to.0 - current.0).signum(), current.1)
+            } else {
+                Position(current.0, current.1 + (to.1 - current.1).signum())
+            };
+            self.connect(current, next);
+            current = next;
+        }
+    }
+
+    fn is_free(&self, position: Position) -> bool {
+        !self.rails.contains_key(&position) && !self.buildings.contains_key(&position)
+    }
+
+    /// Empty tiles next to rails, with the direction a building there would face
+    fn sites(&self) -> Vec<(Position, Direction)> {
+        self.rails
+            .keys()
+            .flat_map(|rail| DIRECTIONS.map(|d| (*rail + d, d.opposite())))
+            .filter(|(position, _)| self.is_free(*position))
+            .collect()
+    }
+
+    fn site_count(&self) -> usize {
+        self.sites()
+            .into_iter()
+            .map(|(position, _)| position)
+            .collect::<BTreeSet<_>>()
+            .len()
+    }
+
+    /// Puts the building down and connects the rail it faces to it
+    fn place(&mut self, building: Building, position: Position, facing: Direction) {
+        self.rails
+            .entry(position + facing)
+            .or_default()
+            .push(facing.opposite());
+        self.buildings
+            .insert(position, GridItem::Building(building, facing));
+    }
+
+    fn place_randomly(
+        &mut self,
+        buildings: Vec<Building>,
+        mut sites: Vec<(Position, Direction)>,
+        rng: &mut StdRng,
+    ) -> Result<(), LayoutError> {
+        sites.shuffle(rng);
+        let mut sites = sites.into_iter();
+        let count = self.buildings.len() + buildings.len();
+        for building in buildings {
+            let (position, facing) = sites
+                .by_ref()
+                .find(|(position, _)| self.is_free(*position))
+                .ok_or(LayoutError::NoRoom { buildings: count })?;
+            self.place(building, position, facing);
+        }
+        Ok(())
+    }
+
+    fn into_grid_items(self) -> GridItems {
+        self.rails
+            .into_iter()
+            .map(|(position, connections)| (position, GridItem::joining(&connections)))
+            .chain(self.buildings)
+            .collect()
+    }
+}
+
+/// Checks that every building can get each component of its recipe from a building that makes
+/// it, and that whatever a building makes can get to something that uses it
+pub fn check_reachable(grid_items: &GridItems) -> Result<(), LayoutError> {
+    let buildings: Vec<(Position, &Building)> = grid_items
+        .iter()
+        .filter_map(|(position, grid_item)| match grid_item {
+            GridItem::Building(b, _) => Some((*position, b)),
+            _ => None,
+        })
+        .collect();
+    let connected = |from: Position, to: Position| calculate_path(from, to, grid_items).is_some();
+
+    for (position, building) in &buildings {
+        for component in building.recipe().components.keys() {
+            let reachable = buildings
+                .iter()
+                .filter(|(_, b)| b.item() == component)
+                .any(|(from, _)| connected(*from, *position));
+            if !reachable {
+                return Err(LayoutError::Unreachable {
+                    position: *position,
+                    item: component.id,
+                });
+            }
+        }
+
+        // Items only used by recipes nobody has picked have nowhere to go yet, that's fine
+        let mut consumers = buildings
+            .iter()
+            .filter(|(_, b)| b.recipe().components.contains_key(building.item()))
+            .peekable();
+        if consumers.peek().is_some() && !consumers.any(|(to, _)| connected(*position, *to)) {
+            return Err(LayoutError::Unused {
+                position: *position,
+                item: building.item().id,
+            });
+        }
+    }
+
+    Ok(())
+}
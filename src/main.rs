@@ -1,100 +1,783 @@
-use nannou::prelude::*;
+use nannou::{lyon::lyon_tessellation::Orientation, prelude::*};
 
+mod blueprint;
 mod building;
+mod capture;
+mod cli;
 mod constants;
+mod content;
+mod editor;
+mod game;
 mod generate;
+mod history;
+mod input;
+mod layout;
+mod minimap;
 mod model;
+mod paths;
+mod power;
+mod reliability;
+mod replay;
+mod screen;
+mod settings;
+#[cfg(test)]
+mod snapshot;
+mod statistics;
+mod style;
+mod svg;
+mod theme;
+mod traffic;
 mod train;
 mod view;
 
 use constants::*;
+use game::{Action, Game};
+use input::{Bindings, Command, Input};
+use minimap::Minimap;
 use model::*;
+use replay::{Playback, Replay};
+use screen::{Entry, Menu, Screen};
+use settings::{Setting, Settings, WindowMode};
 
 fn main() {
+    let options = cli::Options::from_args(&Settings::load());
+    if let Some(path) = &options.verify {
+        std::process::exit(replay::verify(path));
+    }
+    if let Some(text) = &options.import_blueprint {
+        std::process::exit(blueprint::import(text));
+    }
+    if let Some(path) = &options.render_svg {
+        std::process::exit(svg::render_file(&options, path));
+    }
+
     nannou::app(model).event(process_event).update(update).run();
 }
 
 fn model(app: &App) -> Model {
-    let window = app.new_window().maximized(true).view(view).build().unwrap();
-    let (grid, items) = generate::generate();
+    // Escape opens and leaves menus, the app only exits through `quit` so that it saves first
+    app.set_exit_on_escape(false);
+    let settings = Settings::load();
+    let window = app.new_window().view(view);
+    let window = match settings.window.mode {
+        WindowMode::Maximized => window.maximized(true),
+        WindowMode::Windowed => window.size(settings.window.width, settings.window.height),
+        WindowMode::Fullscreen => window.fullscreen(),
+    };
+    let window = window.build().unwrap();
+    let options = cli::Options::from_args(&settings);
+    let playback = options.replay.as_ref().map(|path| {
+        let replay = Replay::load(path)
+            .unwrap_or_else(|e| panic!("failed to load replay {}: {e}", path.display()));
+        Playback::new(replay)
+    });
+    let game = match &playback {
+        Some(playback) => playback.replay.start(),
+        None => Game::new(&options.game),
+    };
+    let recording = Replay::new(options.game.clone(), &game);
+    let absolute =
+        |path: &std::path::PathBuf| std::path::absolute(path).unwrap_or_else(|_| path.clone());
+    let capture = capture::Capture {
+        screenshot: options.screenshot.as_ref().map(absolute),
+        frames: options.frames.as_ref().map(absolute),
+        frame_ticks: options.frame_ticks.unwrap_or(1),
+        exit_when_done: options.screenshot.is_some() || options.frames.is_some(),
+        ..Default::default()
+    };
+    // A replay or a capture starts right away, otherwise the world is only a backdrop for the
+    // main menu
+    let screen = if playback.is_some() || capture.exit_when_done {
+        print!("{}", generate::recipe_summary(&game.items));
+        Screen::Playing
+    } else {
+        Screen::MainMenu(Menu::default())
+    };
+
     Model {
         window,
-        grid,
-        items,
-        score: 0,
+        game,
+        editor: editor::Editor {
+            library: blueprint::Library::load(),
+            ..Default::default()
+        },
+        show_power_overlay: false,
+        show_traffic_overlay: false,
+        selected_train: None,
+        recording,
+        record_path: options.record,
+        playback,
+        paused: false,
+        speed: settings.speed,
+        time_accumulator: 0.0,
         skip_next: false,
+        style: options.style,
+        bindings: Bindings::new(&settings.key_bindings),
+        settings,
+        settings_menu: None,
+        screen,
+        new_game: options.game,
+        camera: Camera::default(),
+        show_help: false,
+        show_statistics: false,
+        capture,
     }
 }
 
-fn update(_app: &App, model: &mut Model, update: Update) {
+fn update(app: &App, model: &mut Model, update: Update) {
+    model.style.reload_theme();
+    if model.capture.exiting {
+        quit(app, model);
+    }
     if model.skip_next {
         model.skip_next = false;
         return;
     }
+    if model.paused || !matches!(model.screen, Screen::Playing) {
+        return;
+    }
 
-    for _ in 0..model.grid.trains.len() {
-        let mut train = model.grid.trains.pop_front().unwrap();
-        if train.update(&update, &mut model.grid.grid_items, &mut model.grid.trains) {
-            model.grid.trains.push_back(train);
+    if let Some(path) = model.capture.next_frame() {
+        // Recorded frames are evenly spaced in game time, however long they take to render
+        for _ in 0..model.capture.frame_ticks {
+            step(model);
+        }
+        app.window(model.window).unwrap().capture_frame(path);
+    } else {
+        model.time_accumulator += update.since_last.secs() * model.speed;
+        let mut ticks = 0;
+        while model.time_accumulator >= TICK_LENGTH {
+            if ticks == MAX_TICKS_PER_FRAME {
+                model.time_accumulator = 0.0;
+                break;
+            }
+            model.time_accumulator -= TICK_LENGTH;
+            ticks += 1;
+            step(model);
         }
     }
 
-    for (pos, grid_item) in &model.grid.grid_items {
-        grid_item.update(
-            pos,
-            &update,
-            &model.grid.grid_items,
-            &mut model.grid.trains,
-            &mut model.score,
-        );
+    let finished = match &model.playback {
+        Some(playback) => playback.finished(&model.game),
+        None => false,
+    };
+    if let Some(path) = &model.capture.screenshot {
+        if finished || model.playback.is_none() {
+            app.window(model.window).unwrap().capture_frame(path);
+            model.capture.screenshot = None;
+            model.capture.exiting = model.capture.exit_when_done;
+            return;
+        }
+    }
+    if finished {
+        if model.capture.exit_when_done {
+            model.capture.exiting = true;
+            return;
+        }
+        model.capture.frames = None;
+        model.screen = Screen::Results(Menu::default());
+    }
+}
+
+fn step(model: &mut Model) {
+    match &mut model.playback {
+        Some(playback) => playback.step(&mut model.game),
+        None => model.game.step(),
     }
 }
 
-fn process_event(_app: &App, model: &mut Model, event: Event) {
-    match event {
+fn process_event(app: &App, model: &mut Model, event: Event) {
+    let window_event = match event {
         Event::WindowEvent {
-            simple: Some(Closed),
+            simple: Some(window_event),
             ..
-        } => std::process::exit(0),
+        } => window_event,
         Event::Resumed => {
             model.skip_next = true;
+            return;
+        }
+        _ => return,
+    };
+    let mods = app.keys.mods;
+    match window_event {
+        Closed => quit(app, model),
+        KeyPressed(key) if model.settings_menu.is_some() => {
+            let menu = model.settings_menu.as_mut().unwrap();
+            match key {
+                Key::Up | Key::Down => menu.select(key == Key::Down, Setting::ALL.len()),
+                Key::Left | Key::Right | Key::Return => {
+                    let setting = Setting::ALL[menu.selected];
+                    setting.change(&mut model.settings, key != Key::Left);
+                    apply_settings(app, model);
+                }
+                Key::Escape => model.settings_menu = None,
+                _ if model.bindings.command(Input::Key(key), mods) == Some(Command::Settings) => {
+                    model.settings_menu = None
+                }
+                _ => {}
+            }
+        }
+        KeyPressed(key) if model.screen.menu().is_some() => menu_key(app, model, key),
+        _ if model.screen.menu().is_some() => {}
+        KeyPressed(key) => pressed(app, model, Input::Key(key)),
+        MousePressed(button) => pressed(app, model, Input::Mouse(button)),
+        MouseWheel(delta, _) => {
+            let y = match delta {
+                MouseScrollDelta::LineDelta(_, y) => y,
+                MouseScrollDelta::PixelDelta(position) => position.y as f32,
+            };
+            if y > 0.0 {
+                pressed(app, model, Input::WheelUp);
+            } else if y < 0.0 {
+                pressed(app, model, Input::WheelDown);
+            }
+        }
+        KeyReleased(key) => released(model, Input::Key(key)),
+        MouseReleased(button) => released(model, Input::Mouse(button)),
+        MouseMoved(point) => {
+            if let Some(last) = model.camera.drag {
+                model.camera.pan += point - last;
+                model.camera.drag = Some(point);
+            }
+            if model.camera.minimap_drag {
+                jump_to(app.window_rect(), model, point);
+            }
+            if model.editor.last_used.is_some() {
+                let position = screen_to_grid(app.window_rect(), model, point);
+                drag_tool(model, position);
+            }
         }
         _ => {}
     }
 }
 
+/// Runs the command bound to the input
+fn pressed(app: &App, model: &mut Model, input_pressed: Input) {
+    let Some(command) = model.bindings.command(input_pressed, app.keys.mods) else {
+        return;
+    };
+    let editing = model.playback.is_none();
+    let pasting = matches!(model.editor.tool, editor::Tool::Paste);
+    let on_minimap = on_minimap(app.window_rect(), model, app.mouse.position());
+    let inspecting = !editing || matches!(model.editor.tool, editor::Tool::Inspect);
+    let clicked_train =
+        train_at(app.window_rect(), model, app.mouse.position()).map(|t| t.path.clone());
+    let tool = &mut model.editor.tool;
+    match command {
+        Command::Pause => model.paused = !model.paused,
+        Command::SpeedUp => model.speed = (model.speed * 2.0).min(MAX_SPEED),
+        Command::SlowDown => model.speed = (model.speed / 2.0).max(1.0 / MAX_SPEED),
+        Command::ZoomIn => model.camera.zoom_at(ZOOM_STEP, app.mouse.position()),
+        Command::ZoomOut => model.camera.zoom_at(1.0 / ZOOM_STEP, app.mouse.position()),
+        Command::ResetCamera => model.camera = Camera::default(),
+        Command::Pan => model.camera.drag = Some(app.mouse.position()),
+        Command::Build if on_minimap => {
+            model.camera.minimap_drag = true;
+            jump_to(app.window_rect(), model, app.mouse.position());
+        }
+        Command::Build if inspecting && clicked_train.is_some() => {
+            model.selected_train = clicked_train
+        }
+        Command::Build if editing => {
+            model.selected_train = None;
+            let position = screen_to_grid(app.window_rect(), model, app.mouse.position());
+            use_tool(model, position);
+        }
+        Command::Build => model.selected_train = None,
+        Command::Upgrade if editing => {
+            let position = screen_to_grid(app.window_rect(), model, app.mouse.position());
+            input(model, Action::Upgrade(position));
+        }
+        Command::PowerOverlay => model.show_power_overlay = !model.show_power_overlay,
+        Command::TrafficOverlay => model.show_traffic_overlay = !model.show_traffic_overlay,
+        Command::TrafficWindow => model.game.traffic.next_window(),
+        Command::ResetTraffic => model.game.traffic.reset(),
+        Command::Minimap => model.settings.panels.minimap = !model.settings.panels.minimap,
+        Command::SeekBack | Command::SeekForward if !editing => {
+            let playback = model.playback.as_mut().unwrap();
+            let target = if command == Command::SeekBack {
+                model.game.tick.saturating_sub(SEEK_TICKS)
+            } else {
+                model.game.tick + SEEK_TICKS
+            };
+            model.game = playback.seek(target);
+        }
+        Command::Undo if editing => input(model, Action::Undo),
+        Command::Redo if editing => input(model, Action::Redo),
+        Command::Copy => model.editor.copy_selection(&model.game),
+        Command::SaveBlueprint => {
+            if let Some(clipboard) = model.editor.clipboard.clone() {
+                if let Err(e) = model.editor.library.save(clipboard) {
+                    eprintln!("Failed to save blueprint: {e}");
+                }
+            }
+        }
+        Command::ExportBlueprint => {
+            if let Some(clipboard) = &model.editor.clipboard {
+                println!("{}", clipboard.export());
+            }
+        }
+        Command::PreviousBlueprint => model.editor.cycle_library(false),
+        Command::NextBlueprint => model.editor.cycle_library(true),
+        Command::Rotate if pasting => {
+            model.editor.clipboard = model.editor.clipboard.as_ref().map(|c| c.rotated());
+        }
+        Command::Mirror if pasting => {
+            model.editor.clipboard = model.editor.clipboard.as_ref().map(|c| c.mirrored());
+        }
+        Command::Rotate if editing => tool.rotate(),
+        Command::NextItem if editing => tool.next_item(&model.game.items),
+        Command::Palette => model.style.palette = model.style.palette.next(),
+        Command::Labels => model.style.labels = !model.style.labels,
+        Command::Theme => model.style.next_theme(),
+        Command::Settings => model.settings_menu = Some(Default::default()),
+        Command::Help => model.show_help = !model.show_help,
+        Command::Statistics => model.show_statistics = !model.show_statistics,
+        Command::Screenshot => match capture::screenshot_path() {
+            Ok(path) => {
+                app.window(model.window).unwrap().capture_frame(&path);
+                println!("Saved screenshot to {}", path.display());
+            }
+            Err(e) => eprintln!("Failed to take screenshot: {e}"),
+        },
+        Command::RecordFrames => match model.capture.toggle_recording() {
+            Ok(()) => {
+                if let Some(directory) = &model.capture.frames {
+                    println!("Recording frames to {}", directory.display());
+                }
+            }
+            Err(e) => eprintln!("Failed to record frames: {e}"),
+        },
+        Command::Menu => model.screen = Screen::Paused(Menu::default()),
+        Command::InspectTool if editing => *tool = editor::Tool::Inspect,
+        Command::RailTool if editing => *tool = editor::Tool::Rail(Orientation::Horizontal),
+        Command::IntersectionTool if editing => {
+            *tool = editor::Tool::Intersection(IntersectionType::Quad)
+        }
+        Command::BuildingTool if editing => *tool = editor::Tool::Building(0, Direction::North),
+        Command::GeneratorTool if editing => *tool = editor::Tool::Generator,
+        Command::PowerPoleTool if editing => *tool = editor::Tool::PowerPole,
+        Command::RotateTool if editing => *tool = editor::Tool::Rotate,
+        Command::SelectTool if editing => *tool = editor::Tool::Select,
+        Command::PasteTool if editing && model.editor.clipboard.is_some() => {
+            *tool = editor::Tool::Paste
+        }
+        Command::RemoveTool if editing => *tool = editor::Tool::Remove,
+        _ => {}
+    }
+}
+
+/// Moves through the screen's menu
+fn menu_key(app: &App, model: &mut Model, key: Key) {
+    let entries = model.screen.entries(model.playback.is_some());
+    let Some(menu) = model.screen.menu_mut() else {
+        return;
+    };
+    let Some(&entry) = entries.get(menu.selected) else {
+        return;
+    };
+    match key {
+        Key::Up | Key::Down => menu.select(key == Key::Down, entries.len()),
+        Key::Left | Key::Right => change(model, entry, key == Key::Right),
+        Key::Return => choose(app, model, entry),
+        Key::Escape => {
+            if let Some(screen) = model.screen.back() {
+                model.screen = screen;
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Changes the value of an option in the new game menu
+fn change(model: &mut Model, entry: Entry, forward: bool) {
+    let options = &mut model.new_game;
+    match entry {
+        Entry::Seed if forward => options.seed = options.seed.wrapping_add(1),
+        Entry::Seed => options.seed = options.seed.wrapping_sub(1),
+        Entry::Layout => options.layout = options.layout.next(),
+        Entry::Difficulty => {
+            let next = generate::Difficulty::of(&options.recipes)
+                .map_or(generate::Difficulty::Normal, |d| d.next());
+            options.recipes = next.constraints();
+        }
+        Entry::Power => options.power = !options.power,
+        Entry::Breakdowns => {
+            options.reliability = match options.reliability {
+                Some(_) => None,
+                None => Some(reliability::Reliability {
+                    failure_rate: DEFAULT_FAILURE_RATE,
+                    repair: reliability::Repair::Auto,
+                    repair_time: DEFAULT_REPAIR_TIME,
+                }),
+            }
+        }
+        _ => {}
+    }
+}
+
+fn choose(app: &App, model: &mut Model, entry: Entry) {
+    match entry {
+        Entry::NewGame => model.screen = Screen::NewGame(Menu::default()),
+        Entry::Load => model.screen = Screen::Load(Menu::default(), screen::saves()),
+        Entry::Settings => model.settings_menu = Some(Menu::default()),
+        Entry::Quit => quit(app, model),
+        Entry::Seed => model.new_game.seed = rand::random::<u32>() as u64,
+        Entry::Layout | Entry::Difficulty | Entry::Power | Entry::Breakdowns => {
+            change(model, entry, true)
+        }
+        Entry::Start => {
+            let options = model.new_game.clone();
+            let game = Game::new(&options);
+            let recording = Replay::new(options, &game);
+            start(model, game, recording);
+        }
+        Entry::Save(i) => {
+            if let Screen::Load(_, saves) = &model.screen {
+                let replay = saves[i].1.clone();
+                start(model, replay.resume(), replay);
+            }
+        }
+        Entry::Resume => model.screen = Screen::Playing,
+        Entry::SaveGame => save_game(model),
+        Entry::EndGame => model.screen = Screen::Results(Menu::default()),
+        Entry::MainMenu => {
+            model.playback = None;
+            model.screen = Screen::MainMenu(Menu::default());
+        }
+        Entry::Back => {
+            if let Some(screen) = model.screen.back() {
+                model.screen = screen;
+            }
+        }
+    }
+}
+
+/// Plays the game, recording onto `recording`
+fn start(model: &mut Model, game: Game, recording: Replay) {
+    print!("{}", generate::recipe_summary(&game.items));
+    model.game = game;
+    model.recording = recording;
+    model.playback = None;
+    model.editor.tool = editor::Tool::Inspect;
+    model.editor.selection = None;
+    model.editor.last_used = None;
+    model.selected_train = None;
+    model.camera = Camera::default();
+    model.paused = false;
+    model.time_accumulator = 0.0;
+    model.screen = Screen::Playing;
+}
+
+/// Writes the game so far to the saves directory, as a replay
+fn save_game(model: &mut Model) {
+    model.recording.final_tick = model.game.tick;
+    model.recording.final_score = model.game.score;
+    let seconds = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let saved = screen::saves_directory().and_then(|directory| {
+        std::fs::create_dir_all(&directory)?;
+        let path = directory.join(format!("save-{seconds}.json"));
+        model.recording.save(&path)?;
+        Ok(path)
+    });
+    match saved {
+        Ok(path) => println!("Saved game to {}", path.display()),
+        Err(e) => eprintln!("Failed to save game: {e}"),
+    }
+}
+
+fn quit(app: &App, model: &mut Model) -> ! {
+    if model.screen.in_game() && model.playback.is_none() {
+        save_recording(model);
+    }
+    save_window_size(app, model);
+    if let Some(window) = app.window(model.window) {
+        if window.await_capture_frame_jobs().is_err() {
+            eprintln!("Timed out saving captured frames");
+        }
+    }
+    std::process::exit(0)
+}
+
+/// Ends what holding the input started. Modifiers are ignored, they may have been let go first
+fn released(model: &mut Model, input_released: Input) {
+    if model.bindings.is_bound(Command::Pan, input_released) {
+        model.camera.drag = None;
+    }
+    if model.bindings.is_bound(Command::Build, input_released) {
+        model.camera.minimap_drag = false;
+    }
+    if model.bindings.is_bound(Command::Build, input_released) && model.editor.last_used.is_some() {
+        if let Some(action) = model.editor.finish_rail() {
+            input(model, action);
+        }
+        if !matches!(model.editor.tool, editor::Tool::Select) {
+            input(model, Action::EndBatch);
+        }
+        model.editor.last_used = None;
+    }
+}
+
+fn use_tool(model: &mut Model, position: Position) {
+    match model.editor.tool {
+        editor::Tool::Select => {
+            model.editor.selection = Some((position, position));
+            model.editor.last_used = Some(position);
+        }
+        editor::Tool::Paste => {
+            let Some(clipboard) = model.editor.clipboard.clone() else {
+                return;
+            };
+            let tiles: Vec<_> = clipboard.placed_at(position, &model.game.items).collect();
+            input(model, Action::BeginBatch);
+            // Buildings of items this world doesn't have are left out
+            for (position, tool) in tiles {
+                if let Some(tool) = tool {
+                    input(model, Action::UseTool(tool, position));
+                }
+            }
+            input(model, Action::EndBatch);
+        }
+        editor::Tool::Rail(..) => {
+            input(model, Action::BeginBatch);
+            model.editor.rail_path = vec![position];
+            model.editor.last_used = Some(position);
+        }
+        tool => {
+            // Dragging keeps using the tool, all of it undone at once
+            input(model, Action::BeginBatch);
+            input(model, Action::UseTool(tool, position));
+            model.editor.last_used = Some(position);
+        }
+    }
+}
+
+fn drag_tool(model: &mut Model, position: Position) {
+    if let (editor::Tool::Select, Some((start, _))) = (model.editor.tool, model.editor.selection) {
+        model.editor.selection = Some((start, position));
+    } else if let editor::Tool::Rail(..) = model.editor.tool {
+        for action in model.editor.drag_rail(position) {
+            input(model, action);
+        }
+        model.editor.last_used = Some(position);
+    } else if model.editor.last_used != Some(position) && model.editor.tool.is_draggable() {
+        input(model, Action::UseTool(model.editor.tool, position));
+        model.editor.last_used = Some(position);
+    }
+}
+
+/// Applies a player's action and records it for the replay
+fn input(model: &mut Model, action: Action) {
+    model.game.apply(&action);
+    model.recording.inputs.push((model.game.tick, action));
+}
+
+fn save_recording(model: &mut Model) {
+    let Some(path) = &model.record_path else {
+        return;
+    };
+    model.recording.final_tick = model.game.tick;
+    model.recording.final_score = model.game.score;
+    if let Err(e) = model.recording.save(path) {
+        eprintln!("Failed to save replay {}: {e}", path.display());
+    }
+}
+
+/// Makes changed settings take effect and saves them
+fn apply_settings(app: &App, model: &mut Model) {
+    let settings = &model.settings;
+    model.style.palette = settings.palette;
+    model.style.labels = settings.labels;
+    if let Err(e) = model.style.load_theme(&settings.theme) {
+        eprintln!("Failed to load theme {}: {e}", settings.theme);
+    }
+    model.speed = settings.speed;
+
+    let window = app.window(model.window).unwrap();
+    window.set_fullscreen(settings.window.mode == WindowMode::Fullscreen);
+    window.set_maximized(settings.window.mode == WindowMode::Maximized);
+    if settings.window.mode == WindowMode::Windowed {
+        window.set_inner_size_points(settings.window.width as f32, settings.window.height as f32);
+    }
+
+    if let Err(e) = settings.save() {
+        eprintln!("Failed to save settings: {e}");
+    }
+}
+
+/// Remembers a windowed window's size for next time
+fn save_window_size(app: &App, model: &mut Model) {
+    let Some(window) = app.window(model.window) else {
+        return;
+    };
+    let (width, height) = window.inner_size_points();
+    let window_settings = &mut model.settings.window;
+    let size = (width as u32, height as u32);
+    if window_settings.mode != WindowMode::Windowed
+        || size == (window_settings.width, window_settings.height)
+    {
+        return;
+    }
+    (window_settings.width, window_settings.height) = size;
+    if let Err(e) = model.settings.save() {
+        eprintln!("Failed to save settings: {e}");
+    }
+}
+
 fn view(app: &App, model: &Model, frame: Frame) {
     let draw = app.draw();
-    let (translation, scale) = center_grid_translation_scale(app.window_rect(), &model.grid);
+    view_game(app, model, &draw, frame.rect());
+    view::draw_screen(&draw, frame.rect(), model);
+    if let Some(menu) = &model.settings_menu {
+        view::draw_settings_menu(&draw, frame.rect(), model, menu);
+    }
+    draw.to_frame(app, &frame).unwrap();
+}
+
+/// The world, and the HUD while in a game
+fn view_game(app: &App, model: &Model, draw: &Draw, window: Rect) {
+    let game = &model.game;
+    let (translation, scale) = grid_translation_scale(window, model);
     let draw_grid = draw.xy(translation).scale(scale);
 
-    draw_grid.background().color(GREY);
+    draw_grid.background().color(model.style.theme.background);
 
-    for (pos, grid_item) in &model.grid.grid_items {
+    for (pos, grid_item) in &game.grid.grid_items {
         let pos = *pos;
-        grid_item.draw_rail(&draw_grid.xy(pos.into()));
+        grid_item.draw_rail(&draw_grid.xy(pos.into()), &model.style);
     }
 
-    for train in &model.grid.trains {
-        train.draw(&draw_grid);
+    for train in &game.grid.trains {
+        train.draw(&draw_grid, &model.style);
     }
 
-    for (pos, grid_item) in &model.grid.grid_items {
+    for (pos, grid_item) in &game.grid.grid_items {
         let pos = *pos;
-        grid_item.draw(&draw_grid.xy(pos.into()));
+        grid_item.draw(&draw_grid.xy(pos.into()), &model.style);
+    }
+
+    for pos in game.grid.breakdowns.keys() {
+        view::draw_breakdown(&draw_grid.xy((*pos).into()), &model.style);
+    }
+
+    if !model.screen.in_game() {
+        return;
+    }
+
+    if let (Some(power), true) = (&game.power, model.show_power_overlay) {
+        view::draw_power_overlay(&draw_grid, &game.grid, power, &model.style);
+    }
+
+    if model.show_traffic_overlay {
+        view::draw_traffic_overlay(&draw_grid, &game.traffic, &model.style);
+    }
+
+    if let Some(selection) = model.editor.selection {
+        view::draw_selection(&draw_grid, selection, &model.style);
+    }
+
+    let routes = model.selected_train.iter().filter_map(|path| {
+        // Only the first of trains on the same path is followed, the others look the same
+        game.grid.trains.iter().find(|t| &t.path == path)
+    });
+    for train in routes {
+        train.draw_route(&draw_grid, &model.style);
+    }
+    if matches!(model.screen, Screen::Playing) {
+        let hovered = screen_to_grid(window, model, app.mouse.position());
+        match train_at(window, model, app.mouse.position()) {
+            Some(train) => train.draw_route(&draw_grid, &model.style),
+            None if matches!(
+                game.grid.grid_items.get(&hovered),
+                Some(GridItem::Building(..))
+            ) =>
+            {
+                // Trains heading to or coming from the building
+                let trains = game.grid.trains.iter().filter(|t| {
+                    t.path.first() == Some(&hovered) || t.path.last() == Some(&hovered)
+                });
+                for train in trains {
+                    train.draw_route(&draw_grid, &model.style);
+                }
+            }
+            None => {}
+        }
+    }
+
+    if model.playback.is_none() && matches!(model.screen, Screen::Playing) {
+        let hovered = screen_to_grid(window, model, app.mouse.position());
+        match (&model.editor.clipboard, model.editor.tool) {
+            (Some(clipboard), editor::Tool::Paste) => {
+                view::draw_paste_preview(&draw_grid, game, &model.style, clipboard, hovered)
+            }
+            _ => view::draw_cursor(&draw_grid.xy(hovered.into()), model, hovered),
+        }
+    }
+
+    let panels = &model.settings.panels;
+    if panels.recipes {
+        view::draw_recipes(draw, window, &game.items, &model.style);
+    }
+    if panels.score {
+        view::draw_score(draw, window, model);
+        view::draw_balance(draw, window, model);
+    }
+    if panels.status {
+        match &model.playback {
+            Some(playback) => view::draw_playback(draw, window, model, playback),
+            None => view::draw_tool(draw, window, model),
+        }
+        if let Some(power) = &game.power {
+            view::draw_power(draw, window, power, &model.style);
+        }
+        if model.show_traffic_overlay {
+            view::draw_traffic(draw, window, &game.traffic, &model.style);
+        }
+    }
+    if panels.minimap {
+        let minimap = Minimap::new(window, &game.grid);
+        minimap.draw(draw, model, grid_viewport(window, model));
+    }
+    if model.show_statistics {
+        view::draw_statistics(draw, window, game, &model.style);
     }
+    if model.show_help {
+        view::draw_help(draw, window, model);
+    }
+}
+
+/// Fits the grid in the window, then applies the camera
+fn grid_translation_scale(rect: Rect, model: &Model) -> (Vec2, f32) {
+    let (translation, scale) = center_grid_translation_scale(rect, &model.game.grid);
+    let camera = &model.camera;
+    (camera.pan + translation * camera.zoom, scale * camera.zoom)
+}
 
-    view::draw_recipes(&draw, frame.rect(), &model.items);
-    view::draw_score(&draw, frame.rect(), model);
+/// Part of the grid in the window, in cells
+fn grid_viewport(rect: Rect, model: &Model) -> Rect {
+    let (translation, scale) = grid_translation_scale(rect, model);
+    let to_cell = |point: Vec2| (point - translation) / scale / CELL_SIZE;
+    Rect::from_corners(to_cell(rect.bottom_left()), to_cell(rect.top_right()))
+}
+
+fn on_minimap(rect: Rect, model: &Model, point: Vec2) -> bool {
+    model.settings.panels.minimap && Minimap::new(rect, &model.game.grid).frame.contains(point)
+}
 
-    draw_grid.to_frame(app, &frame).unwrap();
+/// Pans so that the point of the minimap is in the middle of the window
+fn jump_to(rect: Rect, model: &mut Model, point: Vec2) {
+    let cell = Minimap::new(rect, &model.game.grid).to_grid(point);
+    let (translation, scale) = center_grid_translation_scale(rect, &model.game.grid);
+    let zoom = model.camera.zoom;
+    model.camera.pan = rect.xy() - (translation + cell * CELL_SIZE * scale) * zoom;
 }
 
 fn center_grid_translation_scale(rect: Rect, grid: &Grid) -> (Vec2, f32) {
-    let min_x = grid.grid_items.keys().map(|p| p.0).min().unwrap();
-    let min_y = grid.grid_items.keys().map(|p| p.1).min().unwrap();
-    let max_x = grid.grid_items.keys().map(|p| p.0).max().unwrap();
-    let max_y = grid.grid_items.keys().map(|p| p.1).max().unwrap();
+    // Everything can be removed, an empty grid is shown like a single cell at the origin
+    let (Position(min_x, min_y), Position(max_x, max_y)) =
+        grid.bounds().unwrap_or((Position(0, 0), Position(0, 0)));
     let grid_size = std::cmp::max(max_x, max_y) - std::cmp::min(min_x, min_y);
     let grid_size_px = grid_size as f32 * CELL_SIZE;
     let grid_offset_px = Vec2::new(-min_x as f32, -min_y as f32) * CELL_SIZE;
@@ -109,3 +792,23 @@ fn center_grid_translation_scale(rect: Rect, grid: &Grid) -> (Vec2, f32) {
 
     (translation, new_cell_size / CELL_SIZE)
 }
+
+fn screen_to_grid(rect: Rect, model: &Model, point: Vec2) -> Position {
+    let cell = screen_to_grid_point(rect, model, point) / CELL_SIZE;
+    Position(cell.x.round() as isize, cell.y.round() as isize)
+}
+
+fn screen_to_grid_point(rect: Rect, model: &Model, point: Vec2) -> Vec2 {
+    let (translation, scale) = grid_translation_scale(rect, model);
+    (point - translation) / scale
+}
+
+fn train_at(rect: Rect, model: &Model, point: Vec2) -> Option<&Train> {
+    let point = screen_to_grid_point(rect, model, point);
+    model
+        .game
+        .grid
+        .trains
+        .iter()
+        .find(|t| t.center().distance(point) < TRAIN_HOVER_RADIUS)
+}
@@ -0,0 +1,85 @@
+use nannou::prelude::*;
+
+use crate::{constants::*, model::*};
+
+/// The whole grid drawn small in the top left corner of the window
+pub struct Minimap {
+    pub frame: Rect,
+    /// Grid coordinates of the bottom left cell
+    min: Vec2,
+    /// Size of a cell in pixels
+    cell: f32,
+}
+
+impl Minimap {
+    pub fn new(window: Rect, grid: &Grid) -> Minimap {
+        // With nothing on the grid the minimap shows the cell at the origin
+        let (min, max) = grid.bounds().unwrap_or((Position(0, 0), Position(0, 0)));
+        let extent = std::cmp::max(max.0 - min.0, max.1 - min.1) + 1;
+        Minimap {
+            frame: Rect::from_w_h(MINIMAP_SIZE, MINIMAP_SIZE)
+                .top_left_of(window.pad(MINIMAP_PADDING)),
+            min: Vec2::new(min.0 as f32, min.1 as f32),
+            cell: MINIMAP_SIZE / extent as f32,
+        }
+    }
+
+    /// Grid coordinates, in cells, to window coordinates
+    pub fn to_window(&self, cell: Vec2) -> Vec2 {
+        self.frame.bottom_left() + (cell - self.min + 0.5) * self.cell
+    }
+
+    /// Window coordinates to grid coordinates, in cells
+    pub fn to_grid(&self, point: Vec2) -> Vec2 {
+        (point - self.frame.bottom_left()) / self.cell + self.min - 0.5
+    }
+
+    /// `viewport` is the part of the grid in the window, in cells
+    pub fn draw(&self, draw: &Draw, model: &Model, viewport: Rect) {
+        let style = &model.style;
+        let theme = &style.theme;
+        draw.rect()
+            .xy(self.frame.xy())
+            .wh(self.frame.wh())
+            .color(theme.background)
+            .stroke(theme.outline)
+            .stroke_weight(theme.outline_width);
+
+        for (pos, grid_item) in &model.game.grid.grid_items {
+            let color = match grid_item {
+                GridItem::Building(b, _) => style.color(b.item()),
+                GridItem::Rail(_) => theme.rail.0.color.into_format(),
+                GridItem::Intersection(_) => theme.intersection.0.color.into_format(),
+                GridItem::Generator => theme.generator.0.color.into_format(),
+                GridItem::PowerPole => theme.power_pole.0.color.into_format(),
+            };
+            draw.rect()
+                .xy(self.to_window(Vec2::new(pos.0 as f32, pos.1 as f32)))
+                .w_h(self.cell, self.cell)
+                .color(color);
+        }
+
+        for train in &model.game.grid.trains {
+            let Position(x, y) = train.path[train.position];
+            draw.ellipse()
+                .xy(self.to_window(Vec2::new(x as f32, y as f32)))
+                .radius((self.cell / 2.0).max(MINIMAP_TRAIN_SIZE))
+                .color(train.color(style))
+                .stroke(theme.outline)
+                .stroke_weight(theme.outline_width);
+        }
+
+        let viewport = Rect::from_corners(
+            self.to_window(viewport.bottom_left()),
+            self.to_window(viewport.top_right()),
+        );
+        if let Some(viewport) = viewport.overlap(self.frame) {
+            draw.rect()
+                .xy(viewport.xy())
+                .wh(viewport.wh())
+                .no_fill()
+                .stroke(theme.cursor)
+                .stroke_weight(theme.outline_width * 2.0);
+        }
+    }
+}
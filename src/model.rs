@@ -2,59 +2,152 @@ use std::{
     cell::RefCell,
     collections::{BTreeMap, VecDeque},
     ops::{Add, DerefMut, Mul},
+    path::PathBuf,
 };
 
 use nannou::{lyon::lyon_tessellation::Orientation, prelude::*};
-use rand::{distributions::Standard, prelude::Distribution};
-
-use crate::constants::CELL_SIZE;
+use rand::{distributions::Standard, prelude::Distribution, rngs::StdRng};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    capture::Capture,
+    cli::GameOptions,
+    constants::{CELL_SIZE, MAX_ZOOM, MIN_ZOOM},
+    editor::Editor,
+    game::Game,
+    input::Bindings,
+    replay::{Playback, Replay},
+    screen::{Menu, Screen},
+    settings::Settings,
+    style::Style,
+};
 
 #[derive(Debug, Clone)]
 pub struct Model {
     pub window: window::Id,
-    pub grid: Grid,
-    pub items: Vec<Item>,
-    pub score: usize,
+    pub game: Game,
+    pub editor: Editor,
+    pub show_power_overlay: bool,
+    pub show_traffic_overlay: bool,
+    /// Path of the train whose route is shown, picked with the inspect tool
+    pub selected_train: Option<Vec<Position>>,
+    /// Inputs of this session, saved on exit if requested
+    pub recording: Replay,
+    pub record_path: Option<PathBuf>,
+    /// Some when watching a replay instead of playing
+    pub playback: Option<Playback>,
+    pub paused: bool,
+    /// Multiplier on how fast the game runs
+    pub speed: f64,
+    /// Real time not simulated yet
+    pub time_accumulator: f64,
     pub skip_next: bool,
+    pub style: Style,
+    pub settings: Settings,
+    /// Some while the settings menu is open
+    pub settings_menu: Option<Menu>,
+    pub screen: Screen,
+    /// What the new game menu starts the next game with
+    pub new_game: GameOptions,
+    pub bindings: Bindings,
+    pub camera: Camera,
+    pub show_help: bool,
+    pub show_statistics: bool,
+    pub capture: Capture,
+}
+
+/// Zoom and pan on top of fitting the grid in the window
+#[derive(Debug, Clone)]
+pub struct Camera {
+    pub zoom: f32,
+    pub pan: Vec2,
+    /// Last mouse position while panning
+    pub drag: Option<Vec2>,
+    /// Whether the view follows the mouse around the minimap
+    pub minimap_drag: bool,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Camera {
+            zoom: 1.0,
+            pan: Vec2::ZERO,
+            drag: None,
+            minimap_drag: false,
+        }
+    }
+}
+
+impl Camera {
+    /// Keeps the point where it is on screen
+    pub fn zoom_at(&mut self, factor: f32, point: Vec2) {
+        let zoom = (self.zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+        self.pan = point - (point - self.pan) * (zoom / self.zoom);
+        self.zoom = zoom;
+    }
 }
 
 // === Grid ===
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone)]
 pub struct Grid {
     pub grid_items: GridItems,
     pub trains: VecDeque<Train>,
+    /// Broken buildings and intersections
+    pub breakdowns: BTreeMap<Position, Breakdown>,
+    /// Seeded apart from the generator, so that a game on a saved map plays out the same as one
+    /// on the freshly generated map
+    pub rng: StdRng,
+}
+
+impl Grid {
+    /// Bottom left and top right corners of what's on the grid, None if there's nothing
+    pub fn bounds(&self) -> Option<(Position, Position)> {
+        let xs = || self.grid_items.keys().map(|p| p.0);
+        let ys = || self.grid_items.keys().map(|p| p.1);
+        Some((
+            Position(xs().min()?, ys().min()?),
+            Position(xs().max()?, ys().max()?),
+        ))
+    }
 }
 
 pub type GridItems = BTreeMap<Position, GridItem>;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct Position(pub isize, pub isize);
 
 #[derive(Debug, Clone)]
+#[allow(clippy::large_enum_variant)]
 pub enum GridItem {
     Building(Building, Direction),
     Rail(Orientation),
     Intersection(IntersectionType),
+    /// Supplies power to buildings in reach of poles, as long as it's in reach of a pole itself
+    Generator,
+    PowerPole,
 }
 
 #[derive(Debug, Clone)]
-pub struct Intersection {
-    pub item: Option<Item>,
-    pub cooldown: f64,
+pub struct Breakdown {
+    /// Counts down to an automatic repair
+    pub repair_timer: f64,
+    /// Whether a maintenance train is on its way
+    pub dispatched: bool,
+    /// Counts down to the next try at sending a maintenance train
+    pub dispatch_timer: f64,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum IntersectionType {
     /// Direction is the left corner
-    #[allow(dead_code)]
     Corner(Direction),
     /// Direction is the middle of the three
     Triple(Direction),
     Quad,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Direction {
     North,
     South,
@@ -62,41 +155,110 @@ pub enum Direction {
     West,
 }
 
+pub const DIRECTIONS: [Direction; 4] = [
+    Direction::North,
+    Direction::East,
+    Direction::South,
+    Direction::West,
+];
+
 #[derive(Debug, Clone)]
 pub enum Building {
     Spawner {
         item: Item,
+        /// Index into TIERS
+        tier: RefCell<usize>,
         /// Counts up 0-->item.time
         timer: RefCell<f64>,
         /// Counts down ANIMATION_LENGTH-->0
         spawn_timer: RefCell<f64>,
+        /// Items of the current batch already sent out
+        shipped: RefCell<usize>,
     },
     Crafter {
         item: Item,
+        /// Index into item.recipes
+        recipe: RefCell<usize>,
+        /// Index into TIERS
+        tier: RefCell<usize>,
         contents: RefCell<BTreeMap<Item, usize>>,
         /// Counts up 0-->item.time
         timer: RefCell<f64>,
         /// Counts down ANIMATION_LENGTH-->0
         spawn_timer: RefCell<f64>,
+        /// Items of the current batch already sent out
+        shipped: RefCell<usize>,
     },
     Submitter {
         item: Item,
+        /// Index into item.recipes
+        recipe: RefCell<usize>,
         contents: RefCell<BTreeMap<Item, usize>>,
     },
 }
 
+#[derive(Debug)]
+pub struct BuildingTier {
+    /// Multiplier on how fast the building's timer runs
+    pub speed: f64,
+    /// How many sets of recipe components the building accepts ahead of time
+    pub buffer: usize,
+    /// How many items each craft produces
+    pub output: usize,
+    /// Paid from the balance when upgrading to this tier
+    pub cost: usize,
+}
+
 // === Item ===
 #[derive(Clone, Debug)]
 pub struct Item {
     pub id: usize,
+    pub name: String,
+    /// Short text written on the icon when labels are on
+    pub label: String,
     pub color: Srgb,
+    pub shape: Shape,
+    /// Always has at least one recipe, the first one is the default.
+    /// Spawnable items have a single recipe with no components
+    pub recipes: Vec<Recipe>,
+}
+
+/// What an item's icon looks like
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Shape {
+    #[default]
+    Square,
+    Circle,
+    Triangle,
+    Diamond,
+    Hexagon,
+    Star,
+    Cross,
+}
+
+impl Shape {
+    pub const ALL: [Shape; 7] = [
+        Shape::Square,
+        Shape::Circle,
+        Shape::Triangle,
+        Shape::Diamond,
+        Shape::Hexagon,
+        Shape::Star,
+        Shape::Cross,
+    ];
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Recipe {
     pub components: BTreeMap<Item, usize>,
     pub time: f64,
 }
 
 #[derive(Clone, Debug)]
 pub struct Train {
-    pub item: Item,
+    /// None for maintenance trains, which repair their target instead of delivering
+    pub item: Option<Item>,
     pub path: Vec<Position>,
     pub position: usize,
     pub sub_position: f64,
@@ -112,7 +274,7 @@ impl PartialEq for Item {
 impl Eq for Item {}
 impl PartialOrd for Item {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        self.id.partial_cmp(&other.id)
+        Some(self.cmp(other))
     }
 }
 impl Ord for Item {
@@ -121,19 +283,31 @@ impl Ord for Item {
     }
 }
 
+impl Item {
+    pub fn is_spawnable(&self) -> bool {
+        self.recipes[0].components.is_empty()
+    }
+}
+
 impl GridItem {
+    #[allow(clippy::too_many_arguments)]
     pub fn update(
         &self,
         position: &Position,
-        update: &Update,
+        dt: f64,
         grid_items: &GridItems,
         trains: &mut VecDeque<Train>,
         score: &mut usize,
+        balance: &mut usize,
+        power: f64,
     ) {
         match self {
-            GridItem::Building(b, _) => b.update(position, update, grid_items, trains, score),
+            GridItem::Building(b, _) => {
+                b.update(position, dt, grid_items, trains, score, balance, power)
+            }
             GridItem::Rail(..) => {}
             GridItem::Intersection(_) => {}
+            GridItem::Generator | GridItem::PowerPole => {}
         }
     }
 
@@ -174,6 +348,7 @@ impl GridItem {
             GridItem::Intersection(IntersectionType::Corner(d)) => {
                 vec![self_position + *d, self_position + d.right()]
             }
+            GridItem::Generator | GridItem::PowerPole => vec![],
         }
     }
 }
@@ -223,6 +398,15 @@ impl Direction {
         }
     }
 
+    /// Flipped left to right
+    pub fn mirrored(&self) -> Direction {
+        match self {
+            Direction::East => Direction::West,
+            Direction::West => Direction::East,
+            d => *d,
+        }
+    }
+
     pub fn to_position(self) -> Position {
         // I'm lazy
         Position(0, 0) + self
@@ -236,6 +420,14 @@ impl Direction {
     }
 }
 
+impl Add<Position> for Position {
+    type Output = Position;
+
+    fn add(self, rhs: Position) -> Self::Output {
+        Position(self.0 + rhs.0, self.1 + rhs.1)
+    }
+}
+
 impl Add<Direction> for Position {
     type Output = Position;
 
@@ -0,0 +1,26 @@
+use std::{env, io, path::PathBuf};
+
+/// `$XDG_DATA_HOME/facto_rs`, falling back to `~/.local/share`
+pub fn data_dir() -> io::Result<PathBuf> {
+    app_dir("XDG_DATA_HOME", ".local/share")
+}
+
+/// `$XDG_CONFIG_HOME/facto_rs`, falling back to `~/.config`
+pub fn config_dir() -> io::Result<PathBuf> {
+    app_dir("XDG_CONFIG_HOME", ".config")
+}
+
+/// Errors instead of falling back to the working directory when there's no home to go by
+fn app_dir(variable: &str, in_home: &str) -> io::Result<PathBuf> {
+    let set = |name: &str| env::var_os(name).filter(|value| !value.is_empty());
+    let base = set(variable)
+        .map(PathBuf::from)
+        .or_else(|| set("HOME").map(|home| PathBuf::from(home).join(in_home)))
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("neither {variable} nor HOME is set"),
+            )
+        })?;
+    Ok(base.join("facto_rs"))
+}
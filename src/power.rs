@@ -0,0 +1,81 @@
+use std::collections::BTreeSet;
+
+use crate::{constants::*, model::*};
+
+/// All poles share a single network: generators in reach of a pole feed every building in reach
+/// of a pole. When demand exceeds supply every powered building slows down by the same factor
+#[derive(Debug, Clone, Default)]
+pub struct PowerNetwork {
+    /// Positions within reach of a pole
+    pub covered: BTreeSet<Position>,
+    pub supply: f64,
+    pub demand: f64,
+}
+
+impl PowerNetwork {
+    pub fn calculate(grid_items: &GridItems) -> PowerNetwork {
+        let mut covered = BTreeSet::new();
+        for (pos, grid_item) in grid_items {
+            if matches!(grid_item, GridItem::PowerPole) {
+                covered.extend(in_range(*pos));
+            }
+        }
+
+        let mut supply = 0.0;
+        let mut demand = 0.0;
+        for (pos, grid_item) in grid_items {
+            if !covered.contains(pos) {
+                continue;
+            }
+            match grid_item {
+                GridItem::Generator => supply += GENERATOR_OUTPUT,
+                GridItem::Building(b, _) => demand += b.power_usage(),
+                _ => {}
+            }
+        }
+
+        PowerNetwork {
+            covered,
+            supply,
+            demand,
+        }
+    }
+
+    /// How much of the demand is met, 0-1
+    pub fn satisfaction(&self) -> f64 {
+        if self.demand == 0.0 {
+            1.0
+        } else {
+            (self.supply / self.demand).min(1.0)
+        }
+    }
+
+    /// Speed multiplier for whatever is on this position
+    pub fn factor(&self, position: Position) -> f64 {
+        if self.covered.contains(&position) {
+            self.satisfaction()
+        } else {
+            0.0
+        }
+    }
+}
+
+impl Building {
+    pub fn power_usage(&self) -> f64 {
+        let base = match self {
+            Building::Spawner { .. } => SPAWNER_POWER_USAGE,
+            Building::Crafter { .. } => CRAFTER_POWER_USAGE,
+            Building::Submitter { .. } => 0.0,
+        };
+        base * self.tier().speed
+    }
+}
+
+/// Every position a pole placed here reaches
+pub fn in_range(pole: Position) -> impl Iterator<Item = Position> {
+    let r = POWER_POLE_RADIUS;
+    ((-r)..=r)
+        .flat_map(move |x| ((-r)..=r).map(move |y| (x, y)))
+        .filter(move |(x, y)| x * x + y * y <= r * r)
+        .map(move |(x, y)| Position(pole.0 + x, pole.1 + y))
+}
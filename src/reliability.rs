@@ -0,0 +1,117 @@
+use std::collections::BTreeSet;
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{constants::*, model::*, train::calculate_path_avoiding};
+
+/// Buildings and intersections randomly break down and stop working until repaired
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Reliability {
+    /// Chance for every building and intersection to break down each second
+    pub failure_rate: f64,
+    pub repair: Repair,
+    /// Seconds until an automatic repair, or until one no maintenance train could get to is
+    /// fixed anyway
+    pub repair_time: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Repair {
+    /// Fixed after `repair_time`
+    Auto,
+    /// Fixed when a maintenance train, sent from the closest building, arrives. Falls back to
+    /// `repair_time` while every way there is blocked
+    Maintenance,
+}
+
+impl Reliability {
+    pub fn update(&self, grid: &mut Grid, dt: f64) {
+        match self.repair {
+            Repair::Auto => {
+                grid.breakdowns.retain(|_, breakdown| {
+                    breakdown.repair_timer -= dt;
+                    breakdown.repair_timer > 0.0
+                });
+            }
+            Repair::Maintenance => {
+                let waiting: Vec<Position> = grid
+                    .breakdowns
+                    .iter()
+                    .filter(|(_, breakdown)| {
+                        !breakdown.dispatched && breakdown.dispatch_timer <= 0.0
+                    })
+                    .map(|(position, _)| *position)
+                    .collect();
+                for position in waiting {
+                    let train = maintenance_train(position, grid);
+                    let breakdown = grid.breakdowns.get_mut(&position).unwrap();
+                    match train {
+                        Some(train) => {
+                            grid.trains.push_back(train);
+                            breakdown.dispatched = true;
+                        }
+                        // Searching every building's way there is slow, so not again right away
+                        None => breakdown.dispatch_timer = DISPATCH_RETRY_TIME,
+                    }
+                }
+                grid.breakdowns.retain(|_, breakdown| {
+                    if !breakdown.dispatched {
+                        breakdown.repair_timer -= dt;
+                        breakdown.dispatch_timer -= dt;
+                    }
+                    breakdown.repair_timer > 0.0
+                });
+            }
+        }
+
+        let chance = (self.failure_rate * dt).min(1.0);
+        for (position, grid_item) in &grid.grid_items {
+            let breakable = matches!(
+                grid_item,
+                GridItem::Building(..) | GridItem::Intersection(..)
+            );
+            if breakable && !grid.breakdowns.contains_key(position) && grid.rng.gen_bool(chance) {
+                grid.breakdowns.insert(
+                    *position,
+                    Breakdown {
+                        repair_timer: self.repair_time,
+                        dispatched: false,
+                        dispatch_timer: 0.0,
+                    },
+                );
+            }
+        }
+    }
+}
+
+/// Train from the closest working building that has room to send one out. It goes around
+/// trains waiting to get through the target, since it can't overtake them
+fn maintenance_train(target: Position, grid: &Grid) -> Option<Train> {
+    let avoided: BTreeSet<Position> = grid
+        .trains
+        .iter()
+        .filter(|t| t.path[t.position + 1..].contains(&target))
+        .map(|t| t.path[t.position])
+        .collect();
+
+    grid.grid_items
+        .iter()
+        .filter(|(pos, grid_item)| {
+            matches!(grid_item, GridItem::Building(..))
+                && **pos != target
+                && !grid.breakdowns.contains_key(pos)
+                && !Building::train_full(**pos, &grid.trains)
+        })
+        .filter(|(pos, _)| !avoided.contains(pos))
+        .filter_map(|(pos, _)| {
+            calculate_path_avoiding(*pos, target, &grid.grid_items, avoided.clone())
+        })
+        .min_by_key(|path| path.len())
+        .map(|path| Train {
+            item: None,
+            path,
+            position: 0,
+            sub_position: 0.5,
+        })
+}
@@ -0,0 +1,238 @@
+use std::{fs::File, io, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cli::GameOptions,
+    content::{self, ItemDefinition},
+    editor::Tool,
+    game::{Action, Game},
+    model::*,
+};
+
+/// Everything needed to re-simulate a game: it starts on the saved map and every input is
+/// applied again on the tick it originally happened
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Replay {
+    pub options: GameOptions,
+    pub map: Map,
+    /// In order, each with the tick it was applied on
+    pub inputs: Vec<(u64, Action)>,
+    pub final_tick: u64,
+    pub final_score: usize,
+}
+
+/// The world a game started on, saved in full so that replays don't change along with the
+/// generator
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Map {
+    /// Like in content packs, the last one is the item that gets submitted for points
+    pub items: Vec<ItemDefinition>,
+    pub tiles: Vec<Tile>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Tile {
+    pub position: Position,
+    /// What places it
+    pub tool: Tool,
+    /// Index of the recipe a building starts with
+    #[serde(default)]
+    pub recipe: usize,
+}
+
+/// A replay being watched, drives the game instead of the player
+#[derive(Debug, Clone)]
+pub struct Playback {
+    pub replay: Replay,
+    /// Index of the first input that wasn't applied yet
+    next_input: usize,
+}
+
+impl Replay {
+    /// Records a game from its start
+    pub fn new(options: GameOptions, game: &Game) -> Replay {
+        Replay {
+            options,
+            map: Map::of(game),
+            inputs: vec![],
+            final_tick: 0,
+            final_score: 0,
+        }
+    }
+
+    pub fn load(path: &Path) -> io::Result<Replay> {
+        let replay: Replay = serde_json::from_reader(File::open(path)?)?;
+        replay
+            .map
+            .validate()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok(replay)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        Ok(serde_json::to_writer_pretty(File::create(path)?, self)?)
+    }
+
+    /// Re-simulates the whole replay, returns the score it ends with
+    pub fn simulate(&self) -> usize {
+        self.resume().score
+    }
+
+    /// The game before any input
+    pub fn start(&self) -> Game {
+        let (grid_items, items) = self.map.world();
+        Game::with_world(&self.options, grid_items, items)
+    }
+
+    /// Re-simulates the whole replay, returns the game as it was when recorded
+    pub fn resume(&self) -> Game {
+        let mut playback = Playback::new(self.clone());
+        let mut game = self.start();
+        while !playback.finished(&game) {
+            playback.step(&mut game);
+        }
+        playback.apply_inputs(&mut game);
+        game
+    }
+}
+
+impl Map {
+    pub fn of(game: &Game) -> Map {
+        let tiles = game
+            .grid
+            .grid_items
+            .iter()
+            .filter_map(|(position, grid_item)| {
+                let recipe = match grid_item {
+                    GridItem::Building(
+                        Building::Crafter { recipe, .. } | Building::Submitter { recipe, .. },
+                        _,
+                    ) => *recipe.borrow(),
+                    _ => 0,
+                };
+                Some(Tile {
+                    position: *position,
+                    tool: grid_item.tool(&game.items)?,
+                    recipe,
+                })
+            })
+            .collect();
+        Map {
+            items: game.items.iter().map(ItemDefinition::of).collect(),
+            tiles,
+        }
+    }
+
+    /// Describes the first problem that would keep the map from loading
+    fn validate(&self) -> Result<(), String> {
+        content::validate(&self.items)?;
+        for tile in &self.tiles {
+            match tile.tool {
+                Tool::Building(idx, _) => {
+                    let Some(item) = self.items.get(idx) else {
+                        return Err(format!(
+                            "no item {idx} for the building at {:?}",
+                            tile.position
+                        ));
+                    };
+                    if tile.recipe >= item.recipes.len().max(1) {
+                        return Err(format!("{} has no recipe {}", item.name, tile.recipe));
+                    }
+                }
+                Tool::Rail(..) | Tool::Intersection(..) | Tool::Generator | Tool::PowerPole => {}
+                tool => return Err(format!("{} can't be on a map", tool.name())),
+            }
+        }
+        Ok(())
+    }
+
+    /// The grid and items as they were saved
+    pub fn world(&self) -> (GridItems, Vec<Item>) {
+        let items = content::items(&self.items);
+        let grid_items = self
+            .tiles
+            .iter()
+            .map(|tile| {
+                let grid_item = match tile.tool {
+                    Tool::Building(idx, direction) => GridItem::Building(
+                        Building::new(&items[idx], idx + 1 == items.len(), tile.recipe),
+                        direction,
+                    ),
+                    tool => tool.grid_item(&items).expect("checked by validate"),
+                };
+                (tile.position, grid_item)
+            })
+            .collect();
+        (grid_items, items)
+    }
+}
+
+impl Playback {
+    pub fn new(replay: Replay) -> Playback {
+        Playback {
+            replay,
+            next_input: 0,
+        }
+    }
+
+    pub fn finished(&self, game: &Game) -> bool {
+        game.tick >= self.replay.final_tick
+    }
+
+    /// Applies the inputs for the current tick, then steps. Does nothing once finished
+    pub fn step(&mut self, game: &mut Game) {
+        if self.finished(game) {
+            return;
+        }
+        self.apply_inputs(game);
+        game.step();
+    }
+
+    /// Starts over and fast forwards to `tick`
+    pub fn seek(&mut self, tick: u64) -> Game {
+        self.next_input = 0;
+        let mut game = self.replay.start();
+        while game.tick < tick.min(self.replay.final_tick) {
+            self.step(&mut game);
+        }
+        game
+    }
+
+    fn apply_inputs(&mut self, game: &mut Game) {
+        while let Some((tick, action)) = self.replay.inputs.get(self.next_input) {
+            if *tick > game.tick {
+                break;
+            }
+            game.apply(action);
+            self.next_input += 1;
+        }
+    }
+}
+
+/// Re-simulates a replay file without a window and checks it ends with the recorded score.
+/// Returns the process exit code
+pub fn verify(path: &Path) -> i32 {
+    let replay = match Replay::load(path) {
+        Ok(replay) => replay,
+        Err(e) => {
+            eprintln!("Failed to load replay {}: {e}", path.display());
+            return 1;
+        }
+    };
+
+    let score = replay.simulate();
+    if score == replay.final_score {
+        println!(
+            "Replay verified: score {score} after {} ticks",
+            replay.final_tick
+        );
+        0
+    } else {
+        eprintln!(
+            "Replay mismatch: recorded score {}, got {score}",
+            replay.final_score
+        );
+        1
+    }
+}
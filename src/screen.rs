@@ -0,0 +1,238 @@
+use std::{fs, io, path::PathBuf};
+
+use crate::{constants::TICK_LENGTH, generate::Difficulty, model::*, paths, replay::Replay};
+
+/// What the window shows. The game only runs while `Playing`, the other screens are menus drawn
+/// over it
+#[derive(Debug, Clone)]
+pub enum Screen {
+    MainMenu(Menu),
+    /// Options for the next game
+    NewGame(Menu),
+    /// Saved games to pick from, newest first
+    Load(Menu, Vec<(PathBuf, Replay)>),
+    Playing,
+    Paused(Menu),
+    /// Shown when the game is ended or a replay is over
+    Results(Menu),
+}
+
+/// List of entries with one of them selected
+#[derive(Debug, Clone, Default)]
+pub struct Menu {
+    pub selected: usize,
+}
+
+/// One line of a menu
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Entry {
+    NewGame,
+    Load,
+    Settings,
+    Quit,
+    Seed,
+    Layout,
+    Difficulty,
+    Power,
+    Breakdowns,
+    Start,
+    /// Index into the saves of the load screen
+    Save(usize),
+    Resume,
+    SaveGame,
+    EndGame,
+    MainMenu,
+    Back,
+}
+
+impl Menu {
+    pub fn select(&mut self, down: bool, count: usize) {
+        self.selected = if down {
+            (self.selected + 1) % count
+        } else {
+            (self.selected + count - 1) % count
+        };
+    }
+}
+
+impl Screen {
+    pub fn menu(&self) -> Option<&Menu> {
+        match self {
+            Screen::MainMenu(menu)
+            | Screen::NewGame(menu)
+            | Screen::Load(menu, _)
+            | Screen::Paused(menu)
+            | Screen::Results(menu) => Some(menu),
+            Screen::Playing => None,
+        }
+    }
+
+    pub fn menu_mut(&mut self) -> Option<&mut Menu> {
+        match self {
+            Screen::MainMenu(menu)
+            | Screen::NewGame(menu)
+            | Screen::Load(menu, _)
+            | Screen::Paused(menu)
+            | Screen::Results(menu) => Some(menu),
+            Screen::Playing => None,
+        }
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            Screen::MainMenu(_) => "facto_rs",
+            Screen::NewGame(_) => "New game",
+            Screen::Load(_, _) => "Load",
+            Screen::Playing => "",
+            Screen::Paused(_) => "Paused",
+            Screen::Results(_) => "Results",
+        }
+    }
+
+    /// Playing a replay changes what can be done from the menus
+    pub fn entries(&self, watching: bool) -> Vec<Entry> {
+        match self {
+            Screen::MainMenu(_) => vec![Entry::NewGame, Entry::Load, Entry::Settings, Entry::Quit],
+            Screen::NewGame(_) => vec![
+                Entry::Seed,
+                Entry::Layout,
+                Entry::Difficulty,
+                Entry::Power,
+                Entry::Breakdowns,
+                Entry::Start,
+                Entry::Back,
+            ],
+            Screen::Load(_, saves) => (0..saves.len())
+                .map(Entry::Save)
+                .chain([Entry::Back])
+                .collect(),
+            Screen::Playing => vec![],
+            Screen::Paused(_) if watching => vec![
+                Entry::Resume,
+                Entry::Settings,
+                Entry::EndGame,
+                Entry::MainMenu,
+                Entry::Quit,
+            ],
+            Screen::Paused(_) => vec![
+                Entry::Resume,
+                Entry::SaveGame,
+                Entry::Settings,
+                Entry::EndGame,
+                Entry::MainMenu,
+                Entry::Quit,
+            ],
+            Screen::Results(_) if watching => {
+                vec![Entry::NewGame, Entry::MainMenu, Entry::Quit]
+            }
+            Screen::Results(_) => vec![
+                Entry::NewGame,
+                Entry::SaveGame,
+                Entry::MainMenu,
+                Entry::Quit,
+            ],
+        }
+    }
+
+    /// A game is being played, paused or just ended
+    pub fn in_game(&self) -> bool {
+        matches!(
+            self,
+            Screen::Playing | Screen::Paused(_) | Screen::Results(_)
+        )
+    }
+
+    /// Where Escape leads
+    pub fn back(&self) -> Option<Screen> {
+        match self {
+            Screen::NewGame(_) | Screen::Load(_, _) => Some(Screen::MainMenu(Menu::default())),
+            Screen::Paused(_) => Some(Screen::Playing),
+            _ => None,
+        }
+    }
+}
+
+impl Entry {
+    /// Name and value
+    pub fn text(&self, model: &Model) -> (String, String) {
+        let options = &model.new_game;
+        let on_off = |on: bool| if on { "on" } else { "off" }.to_string();
+        let value = match self {
+            Entry::Seed => options.seed.to_string(),
+            Entry::Layout => options.layout.to_string(),
+            Entry::Difficulty => Difficulty::of(&options.recipes)
+                .map_or("custom", |d| d.name())
+                .to_string(),
+            Entry::Power => on_off(options.power),
+            Entry::Breakdowns => on_off(options.reliability.is_some()),
+            Entry::Save(i) => match &model.screen {
+                Screen::Load(_, saves) => describe(&saves[*i].1),
+                _ => String::new(),
+            },
+            _ => String::new(),
+        };
+        let name = match self {
+            Entry::NewGame => "New game".to_string(),
+            Entry::Load => "Load".to_string(),
+            Entry::Settings => "Settings".to_string(),
+            Entry::Quit => "Quit".to_string(),
+            Entry::Seed => "Seed (enter for a random one)".to_string(),
+            Entry::Layout => "Layout".to_string(),
+            Entry::Difficulty => "Difficulty".to_string(),
+            Entry::Power => "Power".to_string(),
+            Entry::Breakdowns => "Breakdowns".to_string(),
+            Entry::Start => "Start".to_string(),
+            Entry::Save(i) => match &model.screen {
+                Screen::Load(_, saves) => saves[*i]
+                    .0
+                    .file_stem()
+                    .map(|s| s.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+                _ => String::new(),
+            },
+            Entry::Resume => "Resume".to_string(),
+            Entry::SaveGame => "Save".to_string(),
+            Entry::EndGame => "End game".to_string(),
+            Entry::MainMenu => "Main menu".to_string(),
+            Entry::Back => "Back".to_string(),
+        };
+        (name, value)
+    }
+}
+
+/// Seed, score and length of a saved game
+fn describe(replay: &Replay) -> String {
+    format!(
+        "seed {}, score {}, {}",
+        replay.options.seed,
+        replay.final_score,
+        duration(replay.final_tick)
+    )
+}
+
+/// `m:ss` of game time
+pub fn duration(tick: u64) -> String {
+    let seconds = (tick as f64 * TICK_LENGTH) as u64;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+/// `saves` in the data directory
+pub fn saves_directory() -> io::Result<PathBuf> {
+    Ok(paths::data_dir()?.join("saves"))
+}
+
+/// Saved games are replays, newest first. Unreadable files are skipped
+pub fn saves() -> Vec<(PathBuf, Replay)> {
+    let Ok(entries) = saves_directory().and_then(fs::read_dir) else {
+        return vec![];
+    };
+    let mut saves: Vec<(PathBuf, Replay)> = entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let replay = Replay::load(&path).ok()?;
+            Some((path, replay))
+        })
+        .collect();
+    saves.sort_by(|(a, _), (b, _)| b.cmp(a));
+    saves
+}
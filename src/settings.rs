@@ -0,0 +1,274 @@
+use std::{collections::BTreeMap, fs, io, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    constants::*,
+    generate::{Difficulty, RecipeConstraints},
+    layout::Layout,
+    paths,
+    reliability::{Reliability, Repair},
+    style::Palette,
+    theme::BuiltinTheme,
+};
+
+/// Remembered between sessions. Command line options take precedence
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Name of a built-in theme or path of a theme file
+    pub theme: String,
+    pub palette: Palette,
+    pub labels: bool,
+    pub speed: f64,
+    pub window: WindowSettings,
+    pub panels: Panels,
+    /// Command name --> comma separated bindings like `Ctrl+Z`, replacing the command's defaults
+    pub key_bindings: BTreeMap<String, String>,
+    /// What new worlds are generated with
+    pub generator: GeneratorSettings,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowSettings {
+    pub mode: WindowMode,
+    /// Size when windowed
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WindowMode {
+    #[default]
+    Maximized,
+    Windowed,
+    Fullscreen,
+}
+
+/// Parts of the HUD that can be hidden
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Panels {
+    pub recipes: bool,
+    /// Score and balance
+    pub score: bool,
+    /// Current tool or replay progress, and power load
+    pub status: bool,
+    pub minimap: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GeneratorSettings {
+    pub layout: Layout,
+    pub power: bool,
+    pub recipes: RecipeConstraints,
+    pub reliability: Option<Reliability>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            theme: BuiltinTheme::Dark.name().to_string(),
+            palette: Palette::default(),
+            labels: false,
+            speed: 1.0,
+            window: WindowSettings::default(),
+            panels: Panels::default(),
+            key_bindings: BTreeMap::new(),
+            generator: GeneratorSettings::default(),
+        }
+    }
+}
+
+impl Default for WindowSettings {
+    fn default() -> Self {
+        WindowSettings {
+            mode: WindowMode::default(),
+            width: DEFAULT_WINDOW_WIDTH,
+            height: DEFAULT_WINDOW_HEIGHT,
+        }
+    }
+}
+
+impl Default for Panels {
+    fn default() -> Self {
+        Panels {
+            recipes: true,
+            score: true,
+            status: true,
+            minimap: true,
+        }
+    }
+}
+
+impl Settings {
+    /// `settings.toml` in the config directory
+    pub fn path() -> io::Result<PathBuf> {
+        Ok(paths::config_dir()?.join("settings.toml"))
+    }
+
+    /// Defaults if there's no file yet. A broken file is reported and ignored
+    pub fn load() -> Settings {
+        let Ok(path) = Settings::path() else {
+            return Settings::default();
+        };
+        let Ok(text) = fs::read_to_string(&path) else {
+            return Settings::default();
+        };
+        toml::from_str(&text).unwrap_or_else(|e| {
+            eprintln!("Ignoring invalid settings {}: {e}", path.display());
+            Settings::default()
+        })
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let path = Settings::path()?;
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory)?;
+        }
+        let text =
+            toml::to_string(self).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        fs::write(path, text)
+    }
+}
+
+impl WindowMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            WindowMode::Maximized => "maximized",
+            WindowMode::Windowed => "windowed",
+            WindowMode::Fullscreen => "fullscreen",
+        }
+    }
+
+    fn next(&self) -> WindowMode {
+        match self {
+            WindowMode::Maximized => WindowMode::Windowed,
+            WindowMode::Windowed => WindowMode::Fullscreen,
+            WindowMode::Fullscreen => WindowMode::Maximized,
+        }
+    }
+}
+
+/// One line of the settings menu
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Setting {
+    Theme,
+    Palette,
+    Labels,
+    Speed,
+    Window,
+    RecipePanel,
+    ScorePanel,
+    StatusPanel,
+    Minimap,
+    Layout,
+    Difficulty,
+    Power,
+    Reliability,
+}
+
+impl Setting {
+    pub const ALL: [Setting; 13] = [
+        Setting::Theme,
+        Setting::Palette,
+        Setting::Labels,
+        Setting::Speed,
+        Setting::Window,
+        Setting::RecipePanel,
+        Setting::ScorePanel,
+        Setting::StatusPanel,
+        Setting::Minimap,
+        Setting::Layout,
+        Setting::Difficulty,
+        Setting::Power,
+        Setting::Reliability,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Setting::Theme => "Theme",
+            Setting::Palette => "Palette",
+            Setting::Labels => "Labels",
+            Setting::Speed => "Speed",
+            Setting::Window => "Window",
+            Setting::RecipePanel => "Recipe panel",
+            Setting::ScorePanel => "Score panel",
+            Setting::StatusPanel => "Status panel",
+            Setting::Minimap => "Minimap",
+            Setting::Layout => "New game layout",
+            Setting::Difficulty => "New game difficulty",
+            Setting::Power => "New game power",
+            Setting::Reliability => "New game breakdowns",
+        }
+    }
+
+    pub fn value(&self, settings: &Settings) -> String {
+        let on_off = |on: bool| if on { "on" } else { "off" }.to_string();
+        match self {
+            Setting::Theme => settings.theme.clone(),
+            Setting::Palette => settings.palette.name().to_string(),
+            Setting::Labels => on_off(settings.labels),
+            Setting::Speed => format!("x{}", settings.speed),
+            Setting::Window => settings.window.mode.name().to_string(),
+            Setting::RecipePanel => on_off(settings.panels.recipes),
+            Setting::ScorePanel => on_off(settings.panels.score),
+            Setting::StatusPanel => on_off(settings.panels.status),
+            Setting::Minimap => on_off(settings.panels.minimap),
+            Setting::Layout => settings.generator.layout.to_string(),
+            Setting::Difficulty => Difficulty::of(&settings.generator.recipes)
+                .map_or("custom", |d| d.name())
+                .to_string(),
+            Setting::Power => on_off(settings.generator.power),
+            Setting::Reliability => on_off(settings.generator.reliability.is_some()),
+        }
+    }
+
+    /// Moves to the next value, or the previous one if not `forward` where that matters
+    pub fn change(&self, settings: &mut Settings, forward: bool) {
+        match self {
+            Setting::Theme => {
+                let next = match settings.theme.parse::<BuiltinTheme>() {
+                    Ok(builtin) => builtin.next(),
+                    Err(()) => BuiltinTheme::ALL[0],
+                };
+                settings.theme = next.name().to_string();
+            }
+            Setting::Palette => settings.palette = settings.palette.next(),
+            Setting::Labels => settings.labels = !settings.labels,
+            Setting::Speed => {
+                let speed = if forward {
+                    settings.speed * 2.0
+                } else {
+                    settings.speed / 2.0
+                };
+                settings.speed = speed.clamp(1.0 / MAX_SPEED, MAX_SPEED);
+            }
+            Setting::Window => settings.window.mode = settings.window.mode.next(),
+            Setting::RecipePanel => settings.panels.recipes = !settings.panels.recipes,
+            Setting::ScorePanel => settings.panels.score = !settings.panels.score,
+            Setting::StatusPanel => settings.panels.status = !settings.panels.status,
+            Setting::Minimap => settings.panels.minimap = !settings.panels.minimap,
+            Setting::Layout => settings.generator.layout = settings.generator.layout.next(),
+            Setting::Difficulty => {
+                let next = Difficulty::of(&settings.generator.recipes)
+                    .map_or(Difficulty::Normal, |d| d.next());
+                settings.generator.recipes = next.constraints();
+            }
+            Setting::Power => settings.generator.power = !settings.generator.power,
+            Setting::Reliability => {
+                settings.generator.reliability = match settings.generator.reliability {
+                    Some(_) => None,
+                    None => Some(Reliability {
+                        failure_rate: DEFAULT_FAILURE_RATE,
+                        repair: Repair::Auto,
+                        repair_time: DEFAULT_REPAIR_TIME,
+                    }),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,209 @@
+use std::{collections::BTreeMap, fs, path::PathBuf};
+
+use serde::Serialize;
+
+use crate::{
+    cli::GameOptions,
+    content::ContentPack,
+    game::Game,
+    layout::Layout,
+    model::*,
+    reliability::{Reliability, Repair},
+    replay::{Playback, Replay},
+    style::Style,
+    svg,
+};
+
+/// What the simulation looks like at one tick, compared against golden files
+#[derive(Debug, Serialize)]
+struct Snapshot {
+    tick: u64,
+    score: usize,
+    balance: usize,
+    buildings: Vec<BuildingSnapshot>,
+    trains: Vec<TrainSnapshot>,
+    breakdowns: Vec<Position>,
+}
+
+#[derive(Debug, Serialize)]
+struct BuildingSnapshot {
+    position: Position,
+    item: String,
+    tier: Option<usize>,
+    contents: BTreeMap<String, usize>,
+}
+
+#[derive(Debug, Serialize)]
+struct TrainSnapshot {
+    /// None for maintenance trains
+    item: Option<String>,
+    position: Position,
+    sub_position: f64,
+    target: Position,
+}
+
+impl Snapshot {
+    fn of(game: &Game) -> Snapshot {
+        let names = |contents: &BTreeMap<Item, usize>| {
+            contents
+                .iter()
+                .map(|(item, count)| (item.name.clone(), *count))
+                .collect()
+        };
+        let buildings = game
+            .grid
+            .grid_items
+            .iter()
+            .filter_map(|(position, grid_item)| match grid_item {
+                GridItem::Building(b, _) => Some((*position, b)),
+                _ => None,
+            })
+            .map(|(position, b)| {
+                let (tier, contents) = match b {
+                    Building::Spawner { tier, .. } => (Some(*tier.borrow()), BTreeMap::new()),
+                    Building::Crafter { tier, contents, .. } => {
+                        (Some(*tier.borrow()), names(&contents.borrow()))
+                    }
+                    Building::Submitter { contents, .. } => (None, names(&contents.borrow())),
+                };
+                BuildingSnapshot {
+                    position,
+                    item: b.item().name.clone(),
+                    tier,
+                    contents,
+                }
+            })
+            .collect();
+        let trains = game
+            .grid
+            .trains
+            .iter()
+            .map(|t| TrainSnapshot {
+                item: t.item.as_ref().map(|i| i.name.clone()),
+                position: t.path[t.position],
+                sub_position: t.sub_position,
+                target: *t.path.last().unwrap(),
+            })
+            .collect();
+        Snapshot {
+            tick: game.tick,
+            score: game.score,
+            balance: game.balance,
+            buildings,
+            trains,
+            breakdowns: game.grid.breakdowns.keys().copied().collect(),
+        }
+    }
+}
+
+fn golden_directory() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("golden")
+}
+
+/// Compares against the golden file, or rewrites it when `UPDATE_GOLDEN` is set
+fn check(name: &str, actual: &str) {
+    let path = golden_directory().join(name);
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::create_dir_all(golden_directory()).unwrap();
+        fs::write(&path, actual).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&path).unwrap_or_else(|e| {
+        panic!(
+            "Missing golden file {}: {e}. Run with UPDATE_GOLDEN=1 to create it",
+            path.display()
+        )
+    });
+    if expected == actual {
+        return;
+    }
+    let line = expected
+        .lines()
+        .zip(actual.lines())
+        .position(|(e, a)| e != a)
+        .unwrap_or(expected.lines().count().min(actual.lines().count()));
+    panic!(
+        "{} differs from line {}:\n  expected: {}\n    actual: {}\n\
+         Run with UPDATE_GOLDEN=1 if the change is intended",
+        path.display(),
+        line + 1,
+        expected.lines().nth(line).unwrap_or("<end of file>"),
+        actual.lines().nth(line).unwrap_or("<end of file>"),
+    );
+}
+
+/// Snapshots the state and rendering of the game after it ran for `ticks`
+fn check_game(name: &str, mut game: Game, ticks: u64) {
+    while game.tick < ticks {
+        game.step();
+    }
+    let snapshot = serde_json::to_string_pretty(&Snapshot::of(&game)).unwrap() + "\n";
+    check(&format!("{name}.json"), &snapshot);
+    check(
+        &format!("{name}.svg"),
+        &svg::render(&game, &Style::default()),
+    );
+}
+
+fn options(seed: u64, layout: Layout) -> GameOptions {
+    GameOptions {
+        seed,
+        layout,
+        ..GameOptions::default()
+    }
+}
+
+#[test]
+fn plus() {
+    let options = options(6, Layout::Plus);
+    check_game("plus", Game::new(&options), 1200);
+}
+
+#[test]
+fn grid() {
+    let options = options(4, Layout::Grid);
+    check_game("grid", Game::new(&options), 1200);
+}
+
+#[test]
+fn districts_with_power_and_breakdowns() {
+    let options = GameOptions {
+        power: true,
+        reliability: Some(Reliability {
+            failure_rate: 0.02,
+            repair: Repair::Maintenance,
+            repair_time: 5.0,
+        }),
+        ..options(6, Layout::Districts)
+    };
+    check_game("districts", Game::new(&options), 1200);
+}
+
+#[test]
+fn content_pack() {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("content/bakery.toml");
+    let options = GameOptions {
+        content: Some(ContentPack::load(&path).unwrap()),
+        ..options(2, Layout::Ring)
+    };
+    check_game("bakery", Game::new(&options), 1200);
+}
+
+/// Maps are replays: their saved world with their inputs applied
+#[test]
+fn maps() {
+    let directory = golden_directory().join("maps");
+    let mut paths: Vec<PathBuf> = fs::read_dir(&directory)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    paths.sort();
+    for path in paths {
+        let replay = Replay::load(&path).unwrap();
+        let ticks = replay.final_tick;
+        let name = path.file_stem().unwrap().to_string_lossy().to_string();
+        check_game(&name, Playback::new(replay).seek(ticks), ticks);
+    }
+}
@@ -0,0 +1,58 @@
+use std::collections::BTreeMap;
+
+use crate::{constants::*, model::*};
+
+/// Items shipped by buildings, to compare against what the buildings could make at their tiers
+#[derive(Debug, Clone, Default)]
+pub struct Statistics {
+    produced: BTreeMap<Item, u64>,
+    ticks: u64,
+}
+
+/// How much of an item is made, in items per minute
+#[derive(Debug, Clone)]
+pub struct Throughput {
+    pub item: Item,
+    pub buildings: usize,
+    /// What the buildings make at their tiers when never short of components or trains
+    pub capacity: f64,
+    /// What they actually shipped so far
+    pub produced: f64,
+}
+
+impl Statistics {
+    pub fn ship(&mut self, item: &Item) {
+        *self.produced.entry(item.clone()).or_default() += 1;
+    }
+
+    pub fn tick(&mut self) {
+        self.ticks += 1;
+    }
+
+    /// One row per item with a building making it, in the order of the recipes
+    pub fn throughput(&self, grid: &Grid) -> Vec<Throughput> {
+        let minutes = self.ticks as f64 * TICK_LENGTH / 60.0;
+        let mut rows: BTreeMap<&Item, Throughput> = BTreeMap::new();
+        for grid_item in grid.grid_items.values() {
+            let GridItem::Building(b, _) = grid_item else {
+                continue;
+            };
+            if matches!(b, Building::Submitter { .. }) {
+                continue;
+            }
+            let tier = b.tier();
+            let row = rows.entry(b.item()).or_insert_with(|| Throughput {
+                item: b.item().clone(),
+                buildings: 0,
+                capacity: 0.0,
+                produced: match self.produced.get(b.item()) {
+                    Some(&count) if minutes > 0.0 => count as f64 / minutes,
+                    _ => 0.0,
+                },
+            });
+            row.buildings += 1;
+            row.capacity += tier.speed * tier.output as f64 / b.recipe().time * 60.0;
+        }
+        rows.into_values().collect()
+    }
+}
@@ -0,0 +1,169 @@
+use std::path::PathBuf;
+
+use nannou::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    model::*,
+    theme::{BuiltinTheme, Theme, ThemeFile},
+};
+
+/// How things are drawn, changeable while playing
+#[derive(Debug, Clone, Default)]
+pub struct Style {
+    pub palette: Palette,
+    /// Write each item's label on its icon
+    pub labels: bool,
+    pub theme: Theme,
+    /// Where the theme came from, a file is watched for changes
+    pub theme_source: ThemeSource,
+}
+
+#[derive(Debug, Clone)]
+pub enum ThemeSource {
+    Builtin(BuiltinTheme),
+    File(ThemeFile),
+}
+
+impl Default for ThemeSource {
+    fn default() -> Self {
+        ThemeSource::Builtin(BuiltinTheme::Dark)
+    }
+}
+
+/// Item colors. The colorblind palettes replace the generated hues with fixed colors that stay
+/// apart for that kind of color vision
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Palette {
+    #[default]
+    Normal,
+    Deuteranopia,
+    Protanopia,
+    Tritanopia,
+}
+
+/// Okabe and Ito's palette, with white instead of black which vanishes on the background
+const DEUTERANOPIA_COLORS: [(u8, u8, u8); 8] = [
+    (230, 159, 0),
+    (86, 180, 233),
+    (0, 158, 115),
+    (240, 228, 66),
+    (0, 114, 178),
+    (213, 94, 0),
+    (204, 121, 167),
+    (255, 255, 255),
+];
+
+/// Paul Tol's vibrant scheme
+const PROTANOPIA_COLORS: [(u8, u8, u8); 7] = [
+    (238, 119, 51),
+    (0, 119, 187),
+    (51, 187, 238),
+    (238, 51, 119),
+    (204, 51, 17),
+    (0, 153, 136),
+    (187, 187, 187),
+];
+
+/// Leans on red against green and light against dark, which survive missing blue cones
+const TRITANOPIA_COLORS: [(u8, u8, u8); 7] = [
+    (220, 38, 127),
+    (254, 97, 0),
+    (0, 158, 115),
+    (255, 255, 255),
+    (120, 94, 240),
+    (136, 34, 85),
+    (17, 119, 51),
+];
+
+impl Palette {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Palette::Normal => "normal",
+            Palette::Deuteranopia => "deuteranopia",
+            Palette::Protanopia => "protanopia",
+            Palette::Tritanopia => "tritanopia",
+        }
+    }
+
+    pub fn next(&self) -> Palette {
+        match self {
+            Palette::Normal => Palette::Deuteranopia,
+            Palette::Deuteranopia => Palette::Protanopia,
+            Palette::Protanopia => Palette::Tritanopia,
+            Palette::Tritanopia => Palette::Normal,
+        }
+    }
+
+    pub fn color(&self, item: &Item) -> Srgb {
+        let colors: &[(u8, u8, u8)] = match self {
+            Palette::Normal => return item.color,
+            Palette::Deuteranopia => &DEUTERANOPIA_COLORS,
+            Palette::Protanopia => &PROTANOPIA_COLORS,
+            Palette::Tritanopia => &TRITANOPIA_COLORS,
+        };
+        let (r, g, b) = colors[item.id % colors.len()];
+        Srgb::new(r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0)
+    }
+}
+
+impl std::str::FromStr for Palette {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [
+            Palette::Normal,
+            Palette::Deuteranopia,
+            Palette::Protanopia,
+            Palette::Tritanopia,
+        ]
+        .into_iter()
+        .find(|p| p.name() == s)
+        .ok_or(())
+    }
+}
+
+impl Style {
+    pub fn color(&self, item: &Item) -> Srgb {
+        self.palette.color(item)
+    }
+
+    pub fn set_theme(&mut self, builtin: BuiltinTheme) {
+        self.theme = builtin.theme();
+        self.theme_source = ThemeSource::Builtin(builtin);
+    }
+
+    /// A built-in theme's name or a theme file's path
+    pub fn load_theme(&mut self, value: &str) -> Result<(), String> {
+        match value.parse::<BuiltinTheme>() {
+            Ok(builtin) => self.set_theme(builtin),
+            Err(()) => {
+                let (file, theme) = ThemeFile::load(PathBuf::from(value))?;
+                self.theme = theme;
+                self.theme_source = ThemeSource::File(file);
+            }
+        }
+        Ok(())
+    }
+
+    /// Goes through the built-in themes, a file theme is followed by the first one
+    pub fn next_theme(&mut self) {
+        match &self.theme_source {
+            ThemeSource::Builtin(builtin) => self.set_theme(builtin.next()),
+            ThemeSource::File(_) => self.set_theme(BuiltinTheme::ALL[0]),
+        }
+    }
+
+    /// Picks up changes to the theme file. A broken file keeps the last good theme
+    pub fn reload_theme(&mut self) {
+        let ThemeSource::File(file) = &mut self.theme_source else {
+            return;
+        };
+        match file.reload() {
+            Some(Ok(theme)) => self.theme = theme,
+            Some(Err(e)) => eprintln!("Invalid theme {}: {e}", file.path.display()),
+            None => {}
+        }
+    }
+}
@@ -0,0 +1,485 @@
+use std::{cell::RefCell, collections::BTreeMap, fmt::Write, fs, path::Path};
+
+use nannou::{
+    color::{IntoLinSrgba, Srgba},
+    prelude::*,
+};
+
+use crate::{
+    cli::Options,
+    constants::*,
+    game::Game,
+    model::*,
+    replay::{Playback, Replay},
+    style::Style,
+    view::{self, soften},
+};
+
+/// Renders the world to an SVG file without a window, after simulating the requested time.
+/// Returns the process exit code
+pub fn render_file(options: &Options, path: &Path) -> i32 {
+    let ticks = (options.time / TICK_LENGTH).round() as u64;
+    let mut game = match &options.replay {
+        Some(replay_path) => match Replay::load(replay_path) {
+            Ok(replay) => Playback::new(replay).seek(ticks),
+            Err(e) => {
+                eprintln!("Failed to load replay {}: {e}", replay_path.display());
+                return 1;
+            }
+        },
+        None => Game::new(&options.game),
+    };
+    // Replays end, the world keeps going without inputs
+    while game.tick < ticks {
+        game.step();
+    }
+
+    match fs::write(path, render(&game, &options.style)) {
+        Ok(()) => {
+            println!("Rendered {} after {} ticks", path.display(), game.tick);
+            0
+        }
+        Err(e) => {
+            eprintln!("Failed to write {}: {e}", path.display());
+            1
+        }
+    }
+}
+
+/// The grid as the window draws it, with the recipe panel to its right
+pub fn render(game: &Game, style: &Style) -> String {
+    let grid = &game.grid;
+    // Edits can remove every tile, leaving only the recipe panel next to an empty cell
+    let (min, max) = grid.bounds().unwrap_or((Position(0, 0), Position(0, 0)));
+    let (min, max) = (Vec2::from(min), Vec2::from(max));
+    let grid_rect = Rect::from_corners(min - CELL_SIZE, max + CELL_SIZE);
+    let panel_rect = Rect::from_wh(view::recipe_panel_size(&game.items))
+        .top_left_of(grid_rect)
+        .shift_x(grid_rect.w());
+    let document = Rect::from_corners(
+        grid_rect.bottom_left().min(panel_rect.bottom_left()),
+        grid_rect.top_right().max(panel_rect.top_right()),
+    );
+
+    let elements = RefCell::new(vec![]);
+    let canvas = Canvas {
+        elements: &elements,
+        offset: Vec2::ZERO,
+        rotation: 0.0,
+    };
+    canvas.rect(document, Some(paint(style.theme.background)), None);
+    for (pos, grid_item) in &grid.grid_items {
+        draw_rails(&canvas.xy((*pos).into()), grid_item, style);
+    }
+    for train in &grid.trains {
+        draw_train(&canvas, train, style);
+    }
+    for (pos, grid_item) in &grid.grid_items {
+        draw_grid_item(&canvas.xy((*pos).into()), grid_item, style);
+    }
+    for pos in grid.breakdowns.keys() {
+        draw_breakdown(&canvas.xy((*pos).into()), style);
+    }
+    draw_recipes(&canvas, panel_rect, &game.items, style);
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{} {} {} {}\" width=\"{}\" height=\"{}\">\n",
+        number(document.left()),
+        number(-document.top()),
+        number(document.w()),
+        number(document.h()),
+        number(document.w()),
+        number(document.h()),
+    );
+    for element in elements.into_inner() {
+        svg.push_str(&element);
+        svg.push('\n');
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Like nannou's `Draw`, every transform gives a new canvas that draws into the same document
+#[derive(Clone, Copy)]
+struct Canvas<'a> {
+    elements: &'a RefCell<Vec<String>>,
+    offset: Vec2,
+    rotation: f32,
+}
+
+/// A color as SVG attribute values
+#[derive(Debug, Clone)]
+struct Paint {
+    hex: String,
+    opacity: f32,
+}
+
+fn paint(color: impl IntoLinSrgba<f32>) -> Paint {
+    let color: Srgba<u8> = Srgba::from_linear(color.into_lin_srgba()).into_format();
+    Paint {
+        hex: format!("#{:02x}{:02x}{:02x}", color.red, color.green, color.blue),
+        opacity: color.alpha as f32 / 255.0,
+    }
+}
+
+/// Coordinates with two decimals, so that output is stable
+fn number(n: f32) -> String {
+    let text = format!("{n:.2}");
+    match text.as_str() {
+        "-0.00" => "0.00".to_string(),
+        _ => text,
+    }
+}
+
+impl Canvas<'_> {
+    fn xy(&self, v: Vec2) -> Self {
+        Canvas {
+            offset: self.offset + v.rotate(self.rotation),
+            ..*self
+        }
+    }
+
+    fn rotate(&self, angle: f32) -> Self {
+        Canvas {
+            rotation: self.rotation + angle,
+            ..*self
+        }
+    }
+
+    /// Document coordinates, where y points down
+    fn point(&self, p: Vec2) -> Vec2 {
+        let p = self.offset + p.rotate(self.rotation);
+        Vec2::new(p.x, -p.y)
+    }
+
+    fn points(&self, points: impl IntoIterator<Item = Vec2>) -> String {
+        let points: Vec<String> = points
+            .into_iter()
+            .map(|p| {
+                let p = self.point(p);
+                format!("{},{}", number(p.x), number(p.y))
+            })
+            .collect();
+        points.join(" ")
+    }
+
+    fn push(&self, element: String) {
+        self.elements.borrow_mut().push(element);
+    }
+
+    fn line(&self, from: Vec2, to: Vec2, weight: f32, color: Paint) {
+        self.polyline([from, to], weight, color);
+    }
+
+    fn polyline(&self, points: impl IntoIterator<Item = Vec2>, weight: f32, color: Paint) {
+        self.push(format!(
+            "<polyline points=\"{}\" fill=\"none\"{} stroke-linejoin=\"round\"/>",
+            self.points(points),
+            stroke(&Some((color, weight)))
+        ));
+    }
+
+    fn polygon(
+        &self,
+        points: impl IntoIterator<Item = Vec2>,
+        fill_color: Option<Paint>,
+        stroke_color: Option<(Paint, f32)>,
+    ) {
+        self.push(format!(
+            "<polygon points=\"{}\"{}{}/>",
+            self.points(points),
+            fill(&fill_color),
+            stroke(&stroke_color)
+        ));
+    }
+
+    /// Rotates with the canvas, unlike an SVG rect
+    fn rect(&self, frame: Rect, fill_color: Option<Paint>, stroke_color: Option<(Paint, f32)>) {
+        let corners = [
+            frame.top_left(),
+            frame.top_right(),
+            frame.bottom_right(),
+            frame.bottom_left(),
+        ];
+        self.polygon(corners, fill_color, stroke_color);
+    }
+
+    /// Only ever drawn as circles, so rotation doesn't matter
+    fn ellipse(&self, frame: Rect, fill_color: Option<Paint>, stroke_color: Option<(Paint, f32)>) {
+        let center = self.point(frame.xy());
+        self.push(format!(
+            "<ellipse cx=\"{}\" cy=\"{}\" rx=\"{}\" ry=\"{}\"{}{}/>",
+            number(center.x),
+            number(center.y),
+            number(frame.w() / 2.0),
+            number(frame.h() / 2.0),
+            fill(&fill_color),
+            stroke(&stroke_color)
+        ));
+    }
+
+    /// Centered on the point
+    fn text(&self, text: &str, center: Vec2, size: f32, color: Paint) {
+        let center = self.point(center);
+        let mut escaped = String::new();
+        for c in text.chars() {
+            match c {
+                '<' => escaped.push_str("&lt;"),
+                '>' => escaped.push_str("&gt;"),
+                '&' => escaped.push_str("&amp;"),
+                c => escaped.push(c),
+            }
+        }
+        let mut element = format!(
+            "<text x=\"{}\" y=\"{}\" font-size=\"{}\" font-family=\"sans-serif\" \
+             text-anchor=\"middle\" dominant-baseline=\"central\"{}>",
+            number(center.x),
+            number(center.y),
+            number(size),
+            fill(&Some(color))
+        );
+        write!(element, "{escaped}</text>").unwrap();
+        self.push(element);
+    }
+}
+
+fn fill(color: &Option<Paint>) -> String {
+    match color {
+        Some(Paint { hex, opacity }) if *opacity < 1.0 => {
+            format!(" fill=\"{hex}\" fill-opacity=\"{}\"", number(*opacity))
+        }
+        Some(Paint { hex, .. }) => format!(" fill=\"{hex}\""),
+        None => " fill=\"none\"".to_string(),
+    }
+}
+
+fn stroke(color: &Option<(Paint, f32)>) -> String {
+    match color {
+        Some((Paint { hex, opacity }, weight)) if *opacity < 1.0 => format!(
+            " stroke=\"{hex}\" stroke-opacity=\"{}\" stroke-width=\"{}\"",
+            number(*opacity),
+            number(*weight)
+        ),
+        Some((Paint { hex, .. }, weight)) => {
+            format!(" stroke=\"{hex}\" stroke-width=\"{}\"", number(*weight))
+        }
+        None => String::new(),
+    }
+}
+
+// The rest draws view.rs geometry shape for shape
+
+fn draw_rails(canvas: &Canvas, grid_item: &GridItem, style: &Style) {
+    let rail = || paint(style.theme.rail);
+    if let GridItem::Intersection(IntersectionType::Corner(d)) = grid_item {
+        for points in view::corner_rail_points(*d) {
+            canvas.polyline(points, style.theme.rail_width, rail());
+        }
+    }
+    for direction in grid_item.rail_directions() {
+        for [from, to] in view::rail_lines(direction) {
+            canvas.line(from, to, style.theme.rail_width, rail());
+        }
+    }
+}
+
+fn draw_grid_item(canvas: &Canvas, grid_item: &GridItem, style: &Style) {
+    let theme = &style.theme;
+    let outline = || Some((paint(theme.outline), theme.outline_width));
+    match grid_item {
+        GridItem::Building(b, direction) => draw_building(canvas, b, *direction, style),
+        GridItem::Intersection(IntersectionType::Corner(..)) | GridItem::Rail(..) => {}
+        GridItem::Intersection(..) => {
+            canvas.rect(
+                view::intersection_frame(),
+                Some(paint(theme.intersection)),
+                outline(),
+            );
+        }
+        GridItem::Generator => {
+            let frame = view::generator_frame(theme);
+            canvas.rect(frame, Some(paint(theme.generator)), outline());
+            canvas.polyline(view::bolt_points(frame), 4.0 * SIZE_UNIT, paint(theme.bolt));
+        }
+        GridItem::PowerPole => {
+            let frame = view::power_pole_frame();
+            canvas.ellipse(frame, Some(paint(theme.power_pole)), outline());
+            let [from, to] = view::crossbar_points(frame);
+            canvas.line(from, to, 3.0 * SIZE_UNIT, paint(theme.outline));
+        }
+    }
+}
+
+fn draw_building(canvas: &Canvas, b: &Building, direction: Direction, style: &Style) {
+    let theme = &style.theme;
+    let building_size = BUILDING_SIZE * theme.building_scale;
+    let building_frame = view::building_frame(direction, theme);
+    let draw_contents = |contents: &BTreeMap<Item, usize>| {
+        for (item_frame, item) in view::contents_frames(building_frame, contents) {
+            draw_item(canvas, item_frame, item, style);
+        }
+    };
+
+    match b {
+        Building::Spawner {
+            item,
+            tier,
+            timer,
+            spawn_timer,
+            ..
+        } => {
+            let completion = (*timer.borrow() / b.recipe().time) as f32;
+            canvas.polyline(
+                view::loading_arc_points(building_frame, completion),
+                theme.loading_bar_width,
+                paint(theme.loading_bar),
+            );
+            let extra = view::extra_size(*spawn_timer.borrow()) as f32;
+            canvas.ellipse(
+                building_frame.pad(-extra),
+                Some(paint(soften(style.color(item)))),
+                None,
+            );
+            draw_item(canvas, building_frame.pad(building_size / 4.0), item, style);
+            draw_tier(canvas, building_frame, *tier.borrow(), style);
+        }
+        Building::Crafter {
+            item,
+            recipe,
+            tier,
+            contents,
+            timer,
+            spawn_timer,
+            ..
+        } => {
+            let completion = (*timer.borrow() / b.recipe().time).min(1.0) as f32;
+            canvas.xy(building_frame.xy()).polyline(
+                view::loading_square_points(completion, building_size),
+                theme.loading_bar_width,
+                paint(theme.loading_bar),
+            );
+            let extra = view::extra_size(*spawn_timer.borrow()) as f32;
+            canvas.rect(
+                building_frame.pad(-extra),
+                Some(paint(soften(style.color(item)))),
+                None,
+            );
+            draw_item(canvas, view::icon_frame(building_frame), item, style);
+
+            draw_contents(&contents.borrow());
+            draw_recipe_selection(canvas, building_frame, item, *recipe.borrow(), style);
+            draw_tier(canvas, building_frame, *tier.borrow(), style);
+        }
+        Building::Submitter {
+            item,
+            recipe,
+            contents,
+        } => {
+            canvas.polygon(
+                view::submitter_outline(building_frame, direction),
+                Some(paint(style.color(item))),
+                None,
+            );
+
+            draw_contents(&contents.borrow());
+            draw_recipe_selection(canvas, building_frame, item, *recipe.borrow(), style);
+        }
+    }
+}
+
+fn draw_tier(canvas: &Canvas, building_frame: Rect, tier: usize, style: &Style) {
+    for points in view::tier_marks(building_frame, tier) {
+        canvas.polyline(points, 2.0 * SIZE_UNIT, paint(style.theme.outline));
+    }
+}
+
+fn draw_recipe_selection(
+    canvas: &Canvas,
+    building_frame: Rect,
+    item: &Item,
+    selected: usize,
+    style: &Style,
+) {
+    let outline = || paint(style.theme.outline);
+    for (i, dot_frame) in view::recipe_dot_frames(building_frame, item)
+        .into_iter()
+        .enumerate()
+    {
+        let fill = (i == selected).then(outline);
+        canvas.ellipse(
+            dot_frame,
+            fill,
+            Some((outline(), style.theme.outline_width)),
+        );
+    }
+}
+
+fn draw_breakdown(canvas: &Canvas, style: &Style) {
+    let frame = view::breakdown_frame();
+    canvas.polygon(
+        view::breakdown_points(frame),
+        Some(paint(style.theme.breakdown)),
+        Some((paint(style.theme.outline), 2.0 * style.theme.outline_width)),
+    );
+    canvas.text(
+        "!",
+        view::breakdown_mark(frame),
+        BREAKDOWN_MARK_SIZE,
+        paint(style.theme.outline),
+    );
+}
+
+fn draw_train(canvas: &Canvas, train: &Train, style: &Style) {
+    let position = train.path[train.position];
+    let (offset, rotation) = train.placement();
+    let canvas = canvas.xy(Vec2::from(position) + offset).rotate(rotation);
+
+    canvas.rect(Train::frame(), Some(paint(train.color(style))), None);
+    if let Some(item) = &train.item {
+        draw_item(&canvas, Train::icon_frame(), item, style);
+    }
+}
+
+fn draw_item(canvas: &Canvas, frame: Rect, item: &Item, style: &Style) {
+    let color = style.color(item);
+    canvas.polygon(
+        view::item_outline(frame, item.shape),
+        Some(paint(soften(color))),
+        Some((paint(color), SIZE_UNIT)),
+    );
+    if style.labels {
+        canvas.text(
+            &item.label,
+            frame.xy(),
+            (frame.h() * 0.6).max(1.0),
+            paint(style.theme.label),
+        );
+    }
+}
+
+fn draw_recipes(canvas: &Canvas, window: Rect, items: &[Item], style: &Style) {
+    let symbols = || paint(style.theme.recipe_symbols);
+    for row in view::recipe_rows(window, items) {
+        draw_item(canvas, row.result_frame, row.item, style);
+        if row.is_point {
+            canvas.text(
+                "+1",
+                row.result_frame.xy(),
+                ITEM_RECIPE_SIZE * 0.65,
+                paint(style.theme.label),
+            );
+        }
+
+        for [from, to] in row.pluses.into_iter().flatten().chain(row.equals) {
+            canvas.line(from, to, SIZE_UNIT, symbols());
+        }
+        for (frame, component) in row.components {
+            draw_item(canvas, frame, component, style);
+        }
+
+        if let Some((clock_frame, [from, to])) = row.clock {
+            canvas.ellipse(clock_frame, None, Some((symbols(), 2.0 * SIZE_UNIT)));
+            canvas.line(from, to, 2.0 * SIZE_UNIT, symbols());
+        }
+    }
+}
@@ -0,0 +1,254 @@
+use std::{fs, path::PathBuf, time::SystemTime};
+
+use nannou::{
+    color::{IntoLinSrgba, LinSrgba},
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+
+/// Colors and line widths of everything drawn apart from the items themselves. Theme files are
+/// TOML, any field left out is taken from the dark theme
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Theme {
+    pub background: Color,
+    pub rail: Color,
+    /// Borders of intersections, generators, power poles and warning signs, and building marks
+    pub outline: Color,
+    pub intersection: Color,
+    pub generator: Color,
+    pub bolt: Color,
+    pub power_pole: Color,
+    pub power_coverage: Color,
+    pub loading_bar: Color,
+    pub breakdown: Color,
+    pub maintenance_train: Color,
+    /// Heatmap of cells trains pass through freely
+    pub traffic: Color,
+    /// Heatmap of cells trains are stuck waiting in
+    pub congestion: Color,
+    /// Score, balance and the rest of the HUD
+    pub text: Color,
+    /// Item labels and the point marker, written over item icons
+    pub label: Color,
+    /// The `=` and `+` signs of the recipe panel
+    pub recipe_symbols: Color,
+    pub cursor: Color,
+    pub selection: Color,
+    /// Cursor that can't be used, unpowered buildings and brownouts
+    pub invalid: Color,
+    pub rail_width: f32,
+    pub outline_width: f32,
+    pub loading_bar_width: f32,
+    /// Size of buildings and generators relative to the usual
+    pub building_scale: f32,
+}
+
+/// `#rrggbb` or `#rrggbbaa` in theme files
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Color(pub Srgba<u8>);
+
+impl Color {
+    const fn rgba(red: u8, green: u8, blue: u8, alpha: u8) -> Color {
+        Color(Srgba {
+            color: Rgb {
+                red,
+                green,
+                blue,
+                standard: ::core::marker::PhantomData,
+            },
+            alpha,
+        })
+    }
+
+    const fn rgb(red: u8, green: u8, blue: u8) -> Color {
+        Color::rgba(red, green, blue, 255)
+    }
+}
+
+impl IntoLinSrgba<f32> for Color {
+    fn into_lin_srgba(self) -> LinSrgba<f32> {
+        self.0.into_lin_srgba()
+    }
+}
+
+impl TryFrom<String> for Color {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, Self::Error> {
+        parse_color(&text)
+    }
+}
+
+impl From<Color> for String {
+    fn from(Color(c): Color) -> Self {
+        let rgb = format!("#{:02x}{:02x}{:02x}", c.red, c.green, c.blue);
+        match c.alpha {
+            255 => rgb,
+            alpha => format!("{rgb}{alpha:02x}"),
+        }
+    }
+}
+
+pub fn parse_color(text: &str) -> Result<Color, String> {
+    let invalid = || format!("{text} isn't a #rrggbb or #rrggbbaa color");
+    let hex = text.strip_prefix('#').ok_or_else(invalid)?;
+    if !matches!(hex.len(), 6 | 8) || !hex.is_ascii() {
+        return Err(invalid());
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| invalid());
+    let alpha = match hex.len() {
+        8 => channel(6)?,
+        _ => 255,
+    };
+    Ok(Color::rgba(channel(0)?, channel(2)?, channel(4)?, alpha))
+}
+
+/// Themes that don't need a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuiltinTheme {
+    Dark,
+    Light,
+    HighContrast,
+}
+
+impl BuiltinTheme {
+    pub const ALL: [BuiltinTheme; 3] = [
+        BuiltinTheme::Dark,
+        BuiltinTheme::Light,
+        BuiltinTheme::HighContrast,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            BuiltinTheme::Dark => "dark",
+            BuiltinTheme::Light => "light",
+            BuiltinTheme::HighContrast => "high-contrast",
+        }
+    }
+
+    pub fn next(&self) -> BuiltinTheme {
+        match self {
+            BuiltinTheme::Dark => BuiltinTheme::Light,
+            BuiltinTheme::Light => BuiltinTheme::HighContrast,
+            BuiltinTheme::HighContrast => BuiltinTheme::Dark,
+        }
+    }
+
+    pub fn theme(&self) -> Theme {
+        match self {
+            BuiltinTheme::Dark => Theme::default(),
+            BuiltinTheme::Light => Theme {
+                background: Color::rgb(225, 225, 220),
+                rail: Color::rgb(70, 70, 70),
+                outline: Color::rgb(40, 40, 40),
+                intersection: Color::rgb(150, 150, 150),
+                generator: Color::rgb(150, 150, 150),
+                bolt: Color::rgb(230, 180, 0),
+                power_coverage: Color::rgba(255, 180, 0, 50),
+                loading_bar: Color::rgba(60, 60, 60, 70),
+                maintenance_train: Color::rgb(60, 60, 60),
+                text: Color::rgb(20, 20, 20),
+                recipe_symbols: Color::rgb(40, 40, 40),
+                cursor: Color::rgb(20, 20, 20),
+                selection: Color::rgba(0, 0, 0, 30),
+                invalid: Color::rgb(200, 0, 0),
+                ..Theme::default()
+            },
+            BuiltinTheme::HighContrast => Theme {
+                background: Color::rgb(0, 0, 0),
+                rail: Color::rgb(255, 255, 255),
+                outline: Color::rgb(255, 255, 255),
+                intersection: Color::rgb(90, 90, 90),
+                generator: Color::rgb(90, 90, 90),
+                bolt: Color::rgb(255, 255, 0),
+                power_pole: Color::rgb(255, 140, 0),
+                power_coverage: Color::rgba(255, 255, 0, 60),
+                loading_bar: Color::rgba(255, 255, 255, 160),
+                breakdown: Color::rgb(255, 140, 0),
+                maintenance_train: Color::rgb(255, 255, 255),
+                text: Color::rgb(255, 255, 255),
+                label: Color::rgb(0, 0, 0),
+                recipe_symbols: Color::rgb(255, 255, 255),
+                cursor: Color::rgb(255, 255, 0),
+                selection: Color::rgba(255, 255, 0, 50),
+                invalid: Color::rgb(255, 0, 0),
+                rail_width: 4.0,
+                outline_width: 2.0,
+                ..Theme::default()
+            },
+        }
+    }
+}
+
+impl std::str::FromStr for BuiltinTheme {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        BuiltinTheme::ALL
+            .into_iter()
+            .find(|t| t.name() == s)
+            .ok_or(())
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            background: Color::rgb(128, 128, 128),
+            rail: Color::rgb(0, 0, 0),
+            outline: Color::rgb(0, 0, 0),
+            intersection: Color::rgb(169, 169, 169),
+            generator: Color::rgb(169, 169, 169),
+            bolt: Color::rgb(255, 255, 0),
+            power_pole: Color::rgb(139, 69, 19),
+            power_coverage: Color::rgba(255, 220, 0, 40),
+            loading_bar: Color::rgba(60, 60, 60, 100),
+            breakdown: Color::rgb(255, 165, 0),
+            maintenance_train: Color::rgb(255, 255, 255),
+            traffic: Color::rgb(0, 200, 255),
+            congestion: Color::rgb(255, 0, 0),
+            text: Color::rgb(255, 255, 255),
+            label: Color::rgb(0, 0, 0),
+            recipe_symbols: Color::rgb(255, 255, 255),
+            cursor: Color::rgb(255, 255, 255),
+            selection: Color::rgba(255, 255, 255, 40),
+            invalid: Color::rgb(255, 0, 0),
+            rail_width: 2.0,
+            outline_width: 1.0,
+            loading_bar_width: 20.0,
+            building_scale: 1.0,
+        }
+    }
+}
+
+/// A theme file, read again whenever it changes on disk
+#[derive(Debug, Clone)]
+pub struct ThemeFile {
+    pub path: PathBuf,
+    modified: Option<SystemTime>,
+}
+
+impl ThemeFile {
+    pub fn load(path: PathBuf) -> Result<(ThemeFile, Theme), String> {
+        let mut file = ThemeFile {
+            path,
+            modified: None,
+        };
+        let theme = file.read()?;
+        Ok((file, theme))
+    }
+
+    /// The new theme if the file was modified since it was last read
+    pub fn reload(&mut self) -> Option<Result<Theme, String>> {
+        let modified = fs::metadata(&self.path).and_then(|m| m.modified()).ok();
+        (modified != self.modified).then(|| self.read())
+    }
+
+    fn read(&mut self) -> Result<Theme, String> {
+        self.modified = fs::metadata(&self.path).and_then(|m| m.modified()).ok();
+        let text = fs::read_to_string(&self.path).map_err(|e| e.to_string())?;
+        toml::from_str(&text).map_err(|e| e.to_string())
+    }
+}
@@ -0,0 +1,84 @@
+use std::collections::{BTreeMap, VecDeque};
+
+use crate::{constants::*, model::Position};
+
+/// Ticks trains spent in a cell
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Counts {
+    pub occupied: u64,
+    /// Of the occupied ticks, those spent waiting for a slot to free up
+    pub blocked: u64,
+}
+
+/// Where trains spend their time, to find congested intersections. Maintenance trains don't count
+#[derive(Debug, Clone, Default)]
+pub struct Traffic {
+    /// Since the last reset
+    total: BTreeMap<Position, Counts>,
+    /// One per TRAFFIC_BUCKET_TICKS, newest last
+    recent: VecDeque<BTreeMap<Position, Counts>>,
+    ticks: u64,
+    /// Index into TRAFFIC_WINDOWS
+    pub window: usize,
+}
+
+impl Traffic {
+    pub fn occupy(&mut self, position: Position) {
+        self.count(position, |c| c.occupied += 1);
+    }
+
+    pub fn block(&mut self, position: Position) {
+        self.count(position, |c| c.blocked += 1);
+    }
+
+    fn count(&mut self, position: Position, f: impl Fn(&mut Counts)) {
+        f(self.total.entry(position).or_default());
+        if self.recent.is_empty() {
+            self.recent.push_back(BTreeMap::new());
+        }
+        f(self.recent.back_mut().unwrap().entry(position).or_default());
+    }
+
+    pub fn tick(&mut self) {
+        self.ticks += 1;
+        if self.ticks.is_multiple_of(TRAFFIC_BUCKET_TICKS) {
+            self.recent.push_back(BTreeMap::new());
+            if self.recent.len() > TRAFFIC_HISTORY {
+                self.recent.pop_front();
+            }
+        }
+    }
+
+    /// Forgets everything counted so far
+    pub fn reset(&mut self) {
+        *self = Traffic {
+            window: self.window,
+            ..Traffic::default()
+        };
+    }
+
+    pub fn next_window(&mut self) {
+        self.window = (self.window + 1) % TRAFFIC_WINDOWS.len();
+    }
+
+    /// Ticks the counts are taken over, None for everything since the reset
+    pub fn window_ticks(&self) -> Option<u64> {
+        TRAFFIC_WINDOWS[self.window].map(|buckets| buckets as u64 * TRAFFIC_BUCKET_TICKS)
+    }
+
+    /// Counts within the time window
+    pub fn counts(&self) -> BTreeMap<Position, Counts> {
+        let Some(buckets) = TRAFFIC_WINDOWS[self.window] else {
+            return self.total.clone();
+        };
+        let mut counts: BTreeMap<Position, Counts> = BTreeMap::new();
+        for bucket in self.recent.iter().rev().take(buckets) {
+            for (position, c) in bucket {
+                let sum = counts.entry(*position).or_default();
+                sum.occupied += c.occupied;
+                sum.blocked += c.blocked;
+            }
+        }
+        counts
+    }
+}
@@ -1,54 +1,127 @@
-use std::collections::{BTreeSet, VecDeque};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
 
-use nannou::prelude::*;
-
-use crate::{constants::*, model::*};
+use crate::{constants::*, model::*, traffic::Traffic};
 
 impl Train {
     /// Returns true if train should be kept
     pub fn update(
         &mut self,
-        update: &Update,
+        dt: f64,
         grid_items: &mut GridItems,
         trains: &mut VecDeque<Train>,
+        breakdowns: &mut BTreeMap<Position, Breakdown>,
+        traffic: &mut Traffic,
     ) -> bool {
-        if let Some(boundary) = self.about_to_cross_boundary(update) {
-            if self
-                .next_requirements(grid_items)
-                .iter()
-                .any(|s| s.taken(trains))
-            {
+        let is_maintenance = self.item.is_none();
+        if !is_maintenance {
+            traffic.occupy(self.path[self.position]);
+        }
+        if let Some(boundary) = self.about_to_cross_boundary(dt) {
+            if self.next_requirements(grid_items).iter().any(|s| {
+                s.taken(trains)
+                    || self.blocked_by_breakdown(s, grid_items, breakdowns)
+                    || self.in_way_of_maintenance(s, grid_items, trains)
+            }) {
                 self.sub_position = boundary;
+                traffic.block(self.path[self.position]);
                 // don't move
                 return true;
             }
         }
 
         // Move and then submit in the same tick so that we never have to draw an invalid state
-        self.sub_position += update.since_last.secs();
+        self.sub_position += dt;
         if self.sub_position >= 1.0 {
             self.sub_position = 0.0;
             self.position += 1;
+            // Maintenance trains fix broken intersections they come through on the way
+            if is_maintenance {
+                breakdowns.remove(&self.path[self.position]);
+            }
         }
 
         if self.position + 1 == self.path.len() && self.sub_position >= 0.5 {
+            let target = self.path.last().unwrap();
+            let Some(item) = &self.item else {
+                breakdowns.remove(target);
+                return false;
+            };
             let mut contents = grid_items
-                .get_mut(self.path.last().unwrap())
+                .get_mut(target)
                 .expect("train target does not exist")
                 .contents()
                 .expect("train target has no inventory");
 
-            *contents.entry(self.item.clone()).or_default() += 1;
+            *contents.entry(item.clone()).or_default() += 1;
             false
         } else {
             true
         }
     }
 
+    /// Broken intersections can't be entered, except by maintenance trains which fix them. Trains
+    /// that were already in one when it broke drive on through, instead of blocking the way in
+    fn blocked_by_breakdown(
+        &self,
+        slot: &TrainSlot,
+        grid_items: &GridItems,
+        breakdowns: &BTreeMap<Position, Breakdown>,
+    ) -> bool {
+        let is_intersection = matches!(
+            grid_items.get(&slot.position),
+            Some(GridItem::Intersection(..))
+        );
+        let is_entering = slot.position != self.path[self.position];
+        let is_maintenance = self.item.is_none();
+        is_intersection && is_entering && !is_maintenance && breakdowns.contains_key(&slot.position)
+    }
+
+    /// Trains headed through an intersection that a maintenance train is on its way to would only
+    /// queue up in front of it, so they keep off the rest of its route until it's repaired
+    fn in_way_of_maintenance(
+        &self,
+        slot: &TrainSlot,
+        grid_items: &GridItems,
+        trains: &VecDeque<Train>,
+    ) -> bool {
+        if self.item.is_none() || slot.position == self.path[self.position] {
+            return false;
+        }
+        let ahead = &self.path[self.position + 1..];
+        trains.iter().filter(|t| t.item.is_none()).any(|t| {
+            let target = t.path.last().unwrap();
+            matches!(grid_items.get(target), Some(GridItem::Intersection(..)))
+                && ahead.contains(target)
+                && t.path[t.position + 1..].contains(&slot.position)
+        })
+    }
+
+    /// Finds a new way from where the train is to where it's going, after the grid changed.
+    /// Returns false if there is none
+    pub fn reroute(&mut self, grid_items: &GridItems) -> bool {
+        let current = self.path[self.position];
+        let target = *self.path.last().unwrap();
+        if !grid_items.contains_key(&current) || !grid_items.contains_key(&target) {
+            return false;
+        }
+        let Some(new_path) = calculate_path(current, target, grid_items) else {
+            return false;
+        };
+        // Trains can't turn around
+        let previous = self.position.checked_sub(1).map(|p| self.path[p]);
+        if previous.is_some() && new_path.get(1) == previous.as_ref() {
+            return false;
+        }
+
+        self.path.truncate(self.position);
+        self.path.extend(new_path);
+        true
+    }
+
     /// Returns Some(waiting_position) if about to cross a boundary, otherwise None
-    fn about_to_cross_boundary(&self, update: &Update) -> Option<f64> {
+    fn about_to_cross_boundary(&self, dt: f64) -> Option<f64> {
         let before = self.sub_position;
-        let after = before + update.since_last.secs();
+        let after = before + dt;
 
         if before <= TRAIN_BOUNDARY_1 && after > TRAIN_BOUNDARY_1 {
             Some(TRAIN_BOUNDARY_1)
@@ -81,7 +154,7 @@ impl Train {
 
     pub fn next_turn(&self) -> Option<Direction> {
         let position = self.path[self.position];
-        let Some(next_position) = self.path.get(self.position + 1) else {return None};
+        let next_position = self.path.get(self.position + 1)?;
 
         Some(position.direction_towards(*next_position).unwrap())
     }
@@ -95,6 +168,11 @@ impl Train {
         let next_turn = self.next_turn();
 
         match current_slot.part {
+            // Maintenance trains stop in the middle of the intersection they repair
+            SlotPart::Input(..) if is_intersection && next_turn.is_none() => vec![TrainSlot {
+                position: current_slot.position,
+                part: SlotPart::Middle,
+            }],
             SlotPart::Input(..) if is_intersection => vec![
                 TrainSlot {
                     position: current_slot.position,
@@ -133,9 +211,23 @@ impl Train {
     }
 }
 
-pub fn calculate_path(start: Position, target: Position, grid_items: &GridItems) -> Vec<Position> {
+pub fn calculate_path(
+    start: Position,
+    target: Position,
+    grid_items: &GridItems,
+) -> Option<Vec<Position>> {
+    calculate_path_avoiding(start, target, grid_items, BTreeSet::new())
+}
+
+/// Shortest path that doesn't go through any of the `avoided` positions
+pub fn calculate_path_avoiding(
+    start: Position,
+    target: Position,
+    grid_items: &GridItems,
+    avoided: BTreeSet<Position>,
+) -> Option<Vec<Position>> {
     let mut queue = VecDeque::new();
-    let mut explored = BTreeSet::new();
+    let mut explored = avoided;
 
     queue.push_back(vec![start]);
     explored.insert(start);
@@ -143,7 +235,7 @@ pub fn calculate_path(start: Position, target: Position, grid_items: &GridItems)
     while let Some(path) = queue.pop_front() {
         let last = path.last().unwrap();
         if last == &target {
-            return path;
+            return Some(path);
         }
 
         if let Some(grid_item) = grid_items.get(last) {
@@ -158,7 +250,7 @@ pub fn calculate_path(start: Position, target: Position, grid_items: &GridItems)
         }
     }
 
-    unreachable!("no valid path")
+    None
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
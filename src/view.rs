@@ -9,45 +9,47 @@ use nannou::{
     prelude::*,
 };
 
-use crate::{constants::*, model::*};
+use crate::{
+    blueprint::Blueprint,
+    constants::*,
+    editor,
+    game::Game,
+    input::Command,
+    model::*,
+    power::PowerNetwork,
+    replay::Playback,
+    screen::{self, Menu, Screen},
+    settings::Setting,
+    style::Style,
+    theme::{Color, Theme},
+    traffic::Traffic,
+};
 
 impl GridItem {
-    pub fn draw_rail(&self, draw: &Draw) {
+    pub fn draw_rail(&self, draw: &Draw, style: &Style) {
+        if let GridItem::Intersection(IntersectionType::Corner(d)) = self {
+            draw_corner_rail(draw, *d, style);
+        }
+        for direction in self.rail_directions() {
+            draw_rail(draw, direction, style);
+        }
+    }
+
+    /// Where straight rails leave the center of the cell. Corners bend instead
+    pub fn rail_directions(&self) -> Vec<Direction> {
         match self {
-            GridItem::Building(_, direction) => {
-                draw_rail(draw, *direction);
-            }
-            GridItem::Rail(orientation) => {
-                let (dir1, dir2) = match orientation {
-                    Orientation::Horizontal => (Direction::West, Direction::East),
-                    Orientation::Vertical => (Direction::North, Direction::South),
-                };
-                draw_rail(draw, dir1);
-                draw_rail(draw, dir2);
-            }
-            GridItem::Intersection(intersection_type) => {
-                match *intersection_type {
-                    IntersectionType::Corner(d) => {
-                        draw_rail(draw, d);
-                        draw_rail(draw, d.right());
-                    }
-                    IntersectionType::Triple(d) => {
-                        draw_rail(draw, d);
-                        draw_rail(draw, d.left());
-                        draw_rail(draw, d.right());
-                    }
-                    IntersectionType::Quad => {
-                        draw_rail(draw, Direction::East);
-                        draw_rail(draw, Direction::West);
-                        draw_rail(draw, Direction::North);
-                        draw_rail(draw, Direction::South);
-                    }
-                };
-            }
+            GridItem::Building(_, direction) => vec![*direction],
+            GridItem::Rail(Orientation::Horizontal) => vec![Direction::West, Direction::East],
+            GridItem::Rail(Orientation::Vertical) => vec![Direction::North, Direction::South],
+            GridItem::Intersection(IntersectionType::Triple(d)) => vec![*d, d.left(), d.right()],
+            GridItem::Intersection(IntersectionType::Quad) => DIRECTIONS.to_vec(),
+            GridItem::Intersection(IntersectionType::Corner(..))
+            | GridItem::Generator
+            | GridItem::PowerPole => vec![],
         }
     }
 
-    pub fn draw(&self, draw: &Draw) {
+    pub fn draw(&self, draw: &Draw, style: &Style) {
         // draw.rect()
         //     .no_fill()
         //     .w_h(CELL_SIZE, CELL_SIZE)
@@ -55,213 +57,579 @@ impl GridItem {
         //     .stroke_weight(1.0);
 
         match self {
-            GridItem::Building(b, direction) => draw_building(draw, b, *direction),
-            GridItem::Intersection(i_type) => draw_intersection(draw, i_type),
+            GridItem::Building(b, direction) => draw_building(draw, b, *direction, style),
+            GridItem::Intersection(i_type) => draw_intersection(draw, i_type, style),
             GridItem::Rail(..) => {}
+            GridItem::Generator => draw_generator(draw, style),
+            GridItem::PowerPole => draw_power_pole(draw, style),
         }
     }
 }
 
-pub fn draw_building(draw: &Draw, b: &Building, direction: Direction) {
-    let building_frame = {
-        let offset = -(CELL_SIZE - BUILDING_SIZE) / 4.0;
-        let center = Vec2::new(offset, 1.0).rotate(direction.into());
-        Rect::from_xy_wh(center, (BUILDING_SIZE, BUILDING_SIZE).into())
-    };
+pub fn draw_building(draw: &Draw, b: &Building, direction: Direction, style: &Style) {
+    let theme = &style.theme;
+    let building_size = BUILDING_SIZE * theme.building_scale;
+    let building_frame = building_frame(direction, theme);
 
-    fn extra_size(spawn_timer: f64) -> f64 {
-        // Expand
-        lerp(
-            spawn_timer,
-            0.0,
-            ITEM_SPAWN_ANIMATION_TIME - ITEM_SPAWN_ANIMATION_TIME_SHRINK,
-            0.0,
-            LOADING_BAR_WEIGHT,
-        )
-        .unwrap_or(0.0)
-        // Shrink
-            + lerp(
-                spawn_timer,
-                ITEM_SPAWN_ANIMATION_TIME - ITEM_SPAWN_ANIMATION_TIME_SHRINK,
-                ITEM_SPAWN_ANIMATION_TIME,
-                LOADING_BAR_WEIGHT,
-                0.0,
-            )
-            .unwrap_or(0.0)
-    }
-
-    fn draw_contents(draw: &Draw, building_frame: Rect, contents: &BTreeMap<Item, usize>) {
-        let items_frame = building_frame.pad(5.0 * SIZE_UNIT);
-        let mut position = (0, 0);
-        let item_frame = Rect::from_wh(items_frame.wh() / INVENTORY_ITEM_SQUARE_SIDE as f32)
-            .top_left_of(items_frame);
-        for (item, &count) in contents.iter() {
-            for _ in 0..count {
-                let position_px = Vec2::new(position.0 as f32, position.1 as f32);
-                let item_frame = item_frame.shift(item_frame.wh() * position_px);
-                draw.rect()
-                    .xy(item_frame.xy())
-                    .wh(item_frame.pad(2.0 * SIZE_UNIT).wh())
-                    .stroke(item.color)
-                    .stroke_weight(1.0 * SIZE_UNIT)
-                    .color(soften(item.color));
-
-                position.0 += 1;
-                if position.0 >= INVENTORY_ITEM_SQUARE_SIDE {
-                    position.1 -= 1;
-                    position.0 = 0;
-                }
-            }
+    fn draw_contents(
+        draw: &Draw,
+        building_frame: Rect,
+        contents: &BTreeMap<Item, usize>,
+        style: &Style,
+    ) {
+        for (item_frame, item) in contents_frames(building_frame, contents) {
+            draw_item(draw, item_frame, item, style);
         }
     }
 
     match b {
         Building::Spawner {
             item,
+            tier,
             timer,
             spawn_timer,
+            ..
         } => {
             let timer = *timer.borrow();
             let spawn_timer = *spawn_timer.borrow();
 
-            let arc = Arc {
-                center: (building_frame.x(), building_frame.y()).into(),
-                radii: (BUILDING_SIZE / 2.0, BUILDING_SIZE / 2.0).into(),
-                start_angle: Angle::radians(0.0),
-                sweep_angle: lerp(
-                    timer as f32,
-                    0.0,
-                    item.time as f32,
-                    Angle::zero(),
-                    Angle::two_pi(),
-                )
-                .unwrap_or(Angle::two_pi())
-                    * 1.03, // fix visually idk why
-                x_rotation: Angle::radians(0.0),
-            };
-
             draw.path()
                 .stroke()
-                .stroke_weight(2.0 * (LOADING_BAR_WEIGHT as f32))
-                .color(LOADING_BAR_COLOR)
-                .points(arc.flattened(0.1).map(|p| Vec2::from((p.x, p.y))));
+                .stroke_weight(theme.loading_bar_width)
+                .color(theme.loading_bar)
+                .points(loading_arc_points(
+                    building_frame,
+                    (timer / b.recipe().time) as f32,
+                ));
 
             draw.ellipse()
-                .color(soften(item.color))
+                .color(soften(style.color(item)))
                 .xy(building_frame.xy())
                 .wh(building_frame.pad(-(extra_size(spawn_timer) as f32)).wh());
+            draw_item(draw, building_frame.pad(building_size / 4.0), item, style);
+            draw_tier(draw, building_frame, *tier.borrow(), style);
         }
 
         Building::Crafter {
             item,
+            recipe,
+            tier,
             contents,
             timer,
             spawn_timer,
+            ..
         } => {
             let timer = *timer.borrow();
             let spawn_timer = *spawn_timer.borrow();
 
             draw_loading_square_frame(
                 &draw.xy(building_frame.xy()),
-                (timer / item.time).min(1.0) as f32,
-                BUILDING_SIZE,
+                (timer / b.recipe().time).min(1.0) as f32,
+                building_size,
+                theme,
             );
             draw.rect()
                 .xy(building_frame.xy())
                 .wh(building_frame.pad(-extra_size(spawn_timer) as f32).wh())
-                .color(soften(item.color));
+                .color(soften(style.color(item)));
+            draw_item(draw, icon_frame(building_frame), item, style);
 
-            draw_contents(draw, building_frame, &contents.borrow());
+            draw_contents(draw, building_frame, &contents.borrow(), style);
+            draw_recipe_selection(draw, building_frame, item, *recipe.borrow(), style);
+            draw_tier(draw, building_frame, *tier.borrow(), style);
         }
-        Building::Submitter { item, contents } => {
-            let mut point = Vec2::X * BUILDING_SIZE / 3.0 * 2.0;
-            let mut points = vec![];
-
-            const SIDES: usize = 6;
-            for _ in 0..SIDES {
-                points.push(point);
-                point = point.rotate(PI * 2.0 / (SIDES as f32));
-            }
+        Building::Submitter {
+            item,
+            recipe,
+            contents,
+        } => {
+            draw.polygon()
+                .points(submitter_outline(building_frame, direction))
+                .color(style.color(item));
 
-            draw.xy(building_frame.xy())
-                .polygon()
-                .points(points)
-                .rotate(direction.into())
-                .color(item.color);
+            draw_contents(draw, building_frame, &contents.borrow(), style);
+            draw_recipe_selection(draw, building_frame, item, *recipe.borrow(), style);
+        }
+    }
+}
+
+/// What a crafter makes, in the bottom right corner
+pub fn icon_frame(building_frame: Rect) -> Rect {
+    Rect::from_w_h(ICON_SIZE, ICON_SIZE)
+        .bottom_right_of(building_frame)
+        .shift(Vec2::new(ICON_SIZE, -ICON_SIZE) / 2.0)
+}
+
+/// Hexagon with a corner pointing the way the submitter faces
+pub fn submitter_outline(building_frame: Rect, direction: Direction) -> Vec<Vec2> {
+    let radius = building_frame.w() / 3.0 * 2.0;
+    regular_polygon(Vec2::ZERO, radius, 6)
+        .into_iter()
+        .map(|p| building_frame.xy() + p.rotate(angle(direction)))
+        .collect()
+}
+
+fn draw_tier(draw: &Draw, building_frame: Rect, tier: usize, style: &Style) {
+    for points in tier_marks(building_frame, tier) {
+        draw.polyline()
+            .weight(2.0 * SIZE_UNIT)
+            .points(points)
+            .color(style.theme.outline);
+    }
+}
 
-            draw_contents(draw, building_frame, &contents.borrow());
+/// One chevron in the top left corner per upgrade bought
+pub fn tier_marks(building_frame: Rect, tier: usize) -> Vec<[Vec2; 3]> {
+    let mark_frame = Rect::from_w_h(TIER_MARK_SIZE, TIER_MARK_SIZE)
+        .top_left_of(building_frame)
+        .shift(Vec2::new(-TIER_MARK_SIZE, TIER_MARK_SIZE) / 2.0);
+    (0..tier)
+        .map(|i| {
+            let mark_frame = mark_frame.shift_y(-TIER_MARK_SIZE * 0.75 * i as f32);
+            [
+                mark_frame.bottom_left(),
+                mark_frame.mid_top(),
+                mark_frame.bottom_right(),
+            ]
+        })
+        .collect()
+}
+
+fn draw_recipe_selection(
+    draw: &Draw,
+    building_frame: Rect,
+    item: &Item,
+    selected: usize,
+    style: &Style,
+) {
+    for (i, dot_frame) in recipe_dot_frames(building_frame, item)
+        .into_iter()
+        .enumerate()
+    {
+        let dot = draw
+            .ellipse()
+            .xy(dot_frame.xy())
+            .wh(dot_frame.wh())
+            .stroke(style.theme.outline)
+            .stroke_weight(style.theme.outline_width);
+        if i == selected {
+            dot.color(style.theme.outline);
+        } else {
+            dot.no_fill();
         }
     }
 }
 
-fn draw_rail(draw: &Draw, direction: Direction) {
-    let cell_frame = Rect::from_w_h(CELL_SIZE, CELL_SIZE);
-    let draw_rotated = draw.rotate(direction.into());
-
-    draw_rotated
-        .y(BUILDING_SIZE / 6.0)
-        .line()
-        .weight(2.0 * SIZE_UNIT)
-        .points(cell_frame.xy(), cell_frame.mid_right())
-        .color(BLACK);
-    draw_rotated
-        .y(-BUILDING_SIZE / 6.0)
-        .line()
-        .weight(2.0 * SIZE_UNIT)
-        .points(cell_frame.xy(), cell_frame.mid_right())
-        .color(BLACK);
-}
-
-fn draw_intersection(draw: &Draw, _intersection_type: &IntersectionType) {
+/// Row of dots under the building, one per recipe, none if there's nothing to choose from
+pub fn recipe_dot_frames(building_frame: Rect, item: &Item) -> Vec<Rect> {
+    if item.recipes.len() < 2 {
+        return vec![];
+    }
+
+    let dot_frame = Rect::from_w_h(RECIPE_DOT_SIZE, RECIPE_DOT_SIZE)
+        .mid_bottom_of(building_frame)
+        .shift_y(RECIPE_DOT_SIZE * 1.5)
+        .shift_x(-RECIPE_DOT_SIZE * (item.recipes.len() - 1) as f32);
+    (0..item.recipes.len())
+        .map(|i| dot_frame.shift_x(RECIPE_DOT_SIZE * 2.0 * i as f32))
+        .collect()
+}
+
+fn draw_rail(draw: &Draw, direction: Direction, style: &Style) {
+    for [from, to] in rail_lines(direction) {
+        draw.line()
+            .weight(style.theme.rail_width)
+            .points(from, to)
+            .color(style.theme.rail);
+    }
+}
+
+/// Both lanes of a rail from the center of the cell to its edge
+pub fn rail_lines(direction: Direction) -> [[Vec2; 2]; 2] {
     let cell_frame = Rect::from_w_h(CELL_SIZE, CELL_SIZE);
+    [-BUILDING_SIZE / 6.0, BUILDING_SIZE / 6.0].map(|lane| {
+        [cell_frame.xy(), cell_frame.mid_right()]
+            .map(|p| (p + Vec2::new(0.0, lane)).rotate(angle(direction)))
+    })
+}
+
+fn draw_corner_rail(draw: &Draw, d: Direction, style: &Style) {
+    for points in corner_rail_points(d) {
+        draw.path()
+            .stroke()
+            .stroke_weight(style.theme.rail_width)
+            .color(style.theme.rail)
+            .points(points);
+    }
+}
+
+/// Quarter circle arcs around the cell corner between `d` and `d.right()`, one per lane
+pub fn corner_rail_points(d: Direction) -> [Vec<Vec2>; 2] {
+    let pivot = (unit(d) + unit(d.right())) * CELL_SIZE / 2.0;
+    [-BUILDING_SIZE / 6.0, BUILDING_SIZE / 6.0].map(|lane| {
+        let radius = CELL_SIZE / 2.0 + lane;
+        let arc = Arc {
+            center: (pivot.x, pivot.y).into(),
+            radii: (radius, radius).into(),
+            start_angle: Angle::radians(angle(d.left())),
+            sweep_angle: Angle::frac_pi_2(),
+            x_rotation: Angle::radians(0.0),
+        };
+        arc.flattened(0.1).map(|p| Vec2::from((p.x, p.y))).collect()
+    })
+}
+
+fn draw_intersection(draw: &Draw, intersection_type: &IntersectionType, style: &Style) {
+    // Corners are just a bent rail
+    if let IntersectionType::Corner(..) = intersection_type {
+        return;
+    }
     draw.rect()
-        .wh(cell_frame.pad((SLOT_LENGTH as f32) * CELL_SIZE).wh())
-        .stroke_weight(SIZE_UNIT)
-        .stroke_color(BLACK)
-        .color(DARKGRAY);
+        .wh(intersection_frame().wh())
+        .stroke_weight(style.theme.outline_width)
+        .stroke_color(style.theme.outline)
+        .color(style.theme.intersection);
+}
+
+/// The square in the middle of an intersection, inside its slots
+pub fn intersection_frame() -> Rect {
+    Rect::from_w_h(CELL_SIZE, CELL_SIZE).pad((SLOT_LENGTH as f32) * CELL_SIZE)
+}
+
+fn draw_generator(draw: &Draw, style: &Style) {
+    let theme = &style.theme;
+    let frame = generator_frame(theme);
+    draw.rect()
+        .wh(frame.wh())
+        .stroke_weight(theme.outline_width)
+        .stroke_color(theme.outline)
+        .color(theme.generator);
+    draw.polyline()
+        .weight(4.0 * SIZE_UNIT)
+        .points(bolt_points(frame))
+        .color(theme.bolt);
+}
+
+pub fn generator_frame(theme: &Theme) -> Rect {
+    let size = BUILDING_SIZE * theme.building_scale;
+    Rect::from_w_h(size, size)
+}
+
+/// Lightning bolt on a generator
+pub fn bolt_points(generator_frame: Rect) -> [Vec2; 4] {
+    let bolt = generator_frame.pad(generator_frame.w() / 5.0);
+    [
+        bolt.mid_top() + Vec2::X * bolt.w() / 4.0,
+        bolt.xy() - Vec2::X * bolt.w() / 4.0,
+        bolt.xy() + Vec2::X * bolt.w() / 4.0,
+        bolt.mid_bottom() - Vec2::X * bolt.w() / 4.0,
+    ]
+}
+
+fn draw_power_pole(draw: &Draw, style: &Style) {
+    let frame = power_pole_frame();
+    draw.ellipse()
+        .wh(frame.wh())
+        .stroke_weight(style.theme.outline_width)
+        .stroke_color(style.theme.outline)
+        .color(style.theme.power_pole);
+    let [from, to] = crossbar_points(frame);
+    draw.line()
+        .weight(3.0 * SIZE_UNIT)
+        .points(from, to)
+        .color(style.theme.outline);
+}
+
+pub fn power_pole_frame() -> Rect {
+    Rect::from_w_h(CELL_SIZE / 5.0, CELL_SIZE / 5.0)
+}
+
+/// The bar across the top of a power pole, twice as wide as the pole
+pub fn crossbar_points(power_pole_frame: Rect) -> [Vec2; 2] {
+    let size = power_pole_frame.w();
+    [Vec2::new(-size, size / 2.0), Vec2::new(size, size / 2.0)]
+}
+
+/// Pole coverage, and a cross over every building that gets no power
+pub fn draw_power_overlay(draw: &Draw, grid: &Grid, power: &PowerNetwork, style: &Style) {
+    let radius = (POWER_POLE_RADIUS as f32 + 0.5) * CELL_SIZE;
+    for (pos, grid_item) in &grid.grid_items {
+        let draw = draw.xy((*pos).into());
+        match grid_item {
+            GridItem::PowerPole => {
+                draw.ellipse()
+                    .w_h(radius * 2.0, radius * 2.0)
+                    .color(style.theme.power_coverage);
+            }
+            GridItem::Building(..) | GridItem::Generator if power.factor(*pos) == 0.0 => {
+                let frame = Rect::from_w_h(BUILDING_SIZE, BUILDING_SIZE);
+                for (from, to) in [
+                    (frame.bottom_left(), frame.top_right()),
+                    (frame.top_left(), frame.bottom_right()),
+                ] {
+                    draw.line()
+                        .weight(4.0 * SIZE_UNIT)
+                        .points(from, to)
+                        .color(style.theme.invalid);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Cells shaded by how much time trains spend in them, from the traffic color where they pass
+/// freely to the congestion color where they wait
+pub fn draw_traffic_overlay(draw: &Draw, traffic: &Traffic, style: &Style) {
+    let counts = traffic.counts();
+    let Some(busiest) = counts.values().map(|c| c.occupied).max() else {
+        return;
+    };
+    let mix = |a: Color, b: Color, t: f32| {
+        let (a, b): (Srgba, Srgba) = (a.0.into_format(), b.0.into_format());
+        srgba(
+            a.red + (b.red - a.red) * t,
+            a.green + (b.green - a.green) * t,
+            a.blue + (b.blue - a.blue) * t,
+            TRAFFIC_ALPHA * a.alpha,
+        )
+    };
+    for (pos, c) in counts {
+        if c.occupied == 0 {
+            continue;
+        }
+        let heat = c.occupied as f32 / busiest as f32;
+        let congestion = c.blocked as f32 / c.occupied as f32;
+        let mut color = mix(style.theme.traffic, style.theme.congestion, congestion);
+        color.alpha *= heat;
+        draw.rect()
+            .xy(pos.into())
+            .w_h(CELL_SIZE, CELL_SIZE)
+            .color(color);
+    }
+}
+
+/// Time window of the traffic heatmap
+pub fn draw_traffic(draw: &Draw, screen: Rect, traffic: &Traffic, style: &Style) {
+    let frame = Rect::from_w_h(400.0, 50.0)
+        .bottom_left_of(screen.pad(50.0))
+        .shift_y(100.0);
+    let window = match traffic.window_ticks() {
+        Some(ticks) => format!("last {}", screen::duration(ticks)),
+        None => "since reset".to_string(),
+    };
+    draw.text(&format!("Traffic {window}"))
+        .xy(frame.xy())
+        .wh(frame.wh())
+        .font_size(32)
+        .align_text_bottom()
+        .left_justify()
+        .color(style.theme.text);
+}
+
+/// Warning sign over something that's broken
+pub fn draw_breakdown(draw: &Draw, style: &Style) {
+    let frame = breakdown_frame();
+    draw.polygon()
+        .stroke(style.theme.outline)
+        .stroke_weight(2.0 * style.theme.outline_width)
+        .points(breakdown_points(frame))
+        .color(style.theme.breakdown);
+    draw.text("!")
+        .wh(frame.wh())
+        .xy(breakdown_mark(frame))
+        .align_text_middle_y()
+        .font_size(BREAKDOWN_MARK_SIZE as u32)
+        .color(style.theme.outline);
+}
+
+pub fn breakdown_frame() -> Rect {
+    Rect::from_w_h(CELL_SIZE / 2.0, CELL_SIZE / 2.0)
+}
+
+/// The triangle of the warning sign
+pub fn breakdown_points(breakdown_frame: Rect) -> [Vec2; 3] {
+    [
+        breakdown_frame.bottom_left(),
+        breakdown_frame.mid_top(),
+        breakdown_frame.bottom_right(),
+    ]
+}
+
+/// Center of the exclamation mark, a bit low to sit in the wide part of the triangle
+pub fn breakdown_mark(breakdown_frame: Rect) -> Vec2 {
+    Vec2::new(0.0, -breakdown_frame.h() / 8.0)
 }
 
 impl Train {
-    pub fn draw(&self, draw: &Draw) {
-        let position = &self.path[self.position];
-        let direction = self.heading();
-        let draw_rotated = draw.xy((*position).into()).rotate(direction.into());
-        let cell_frame = Rect::from_w_h(CELL_SIZE, CELL_SIZE);
-
-        draw_rotated
-            .rect()
-            .x(cell_frame.mid_left().x
-                + CELL_SIZE * (self.sub_position - TRAIN_LENGTH / 2.0) as f32)
-            .y(-BUILDING_SIZE / 6.0)
-            .color(self.item.color)
-            .w_h(
-                CELL_SIZE * (TRAIN_LENGTH as f32),
-                CELL_SIZE * (TRAIN_LENGTH as f32) / 2.0,
-            );
+    pub fn draw(&self, draw: &Draw, style: &Style) {
+        let position = self.path[self.position];
+        let (offset, rotation) = self.placement();
+        let draw = draw.xy(Vec2::from(position) + offset).rotate(rotation);
+        let train_frame = Train::frame();
+
+        draw.rect().wh(train_frame.wh()).color(self.color(style));
+        if let Some(item) = &self.item {
+            draw_item(&draw, Train::icon_frame(), item, style);
+        }
     }
+
+    /// Around the middle of the train, pointing east
+    pub fn frame() -> Rect {
+        Rect::from_w_h(
+            CELL_SIZE * (TRAIN_LENGTH as f32),
+            CELL_SIZE * (TRAIN_LENGTH as f32) / 2.0,
+        )
+    }
+
+    /// What it carries, in the middle
+    pub fn icon_frame() -> Rect {
+        let height = Train::frame().h();
+        Rect::from_w_h(height, height).pad(SIZE_UNIT)
+    }
+
+    /// Color of the item it carries
+    pub fn color(&self, style: &Style) -> Srgb {
+        self.item
+            .as_ref()
+            .map_or(style.theme.maintenance_train.0.color.into_format(), |i| {
+                style.color(i)
+            })
+    }
+
+    /// Where the train is drawn
+    pub fn center(&self) -> Vec2 {
+        Vec2::from(self.path[self.position]) + self.placement().0
+    }
+
+    /// The rest of the way to its target, ending in a dot on the target
+    pub fn draw_route(&self, draw: &Draw, style: &Style) {
+        let color = self.color(style);
+        let color = srgba(color.red, color.green, color.blue, ROUTE_ALPHA);
+        let target = Vec2::from(*self.path.last().unwrap());
+        let points = std::iter::once(self.center()).chain(
+            self.path[self.position + 1..]
+                .iter()
+                .map(|p| Vec2::from(*p)),
+        );
+        draw.polyline()
+            .weight(ROUTE_WIDTH)
+            .join_round()
+            .points(points)
+            .color(color);
+        draw.ellipse()
+            .xy(target)
+            .radius(ROUTE_WIDTH * 1.5)
+            .color(color);
+    }
+
+    /// Middle of the train relative to its cell, and its rotation. Trains drive on the right
+    /// lane and follow an arc when turning
+    pub fn placement(&self) -> (Vec2, f32) {
+        let position = self.path[self.position];
+        let entry = self
+            .position
+            .checked_sub(1)
+            .and_then(|p| self.path[p].direction_towards(position));
+        let exit = self
+            .path
+            .get(self.position + 1)
+            .and_then(|next| position.direction_towards(*next));
+        let lane = BUILDING_SIZE / 6.0;
+        // The train is drawn behind its front
+        let t = (self.sub_position - TRAIN_LENGTH / 2.0) as f32;
+
+        let straight = |heading: Direction| {
+            let offset = unit(heading) * (t - 0.5) * CELL_SIZE + unit(heading.right()) * lane;
+            (offset, angle(heading))
+        };
+        let (entry, exit) = match (entry, exit) {
+            (Some(entry), Some(exit)) if entry != exit && t >= 0.0 => (entry, exit),
+            (Some(entry), _) if t < 0.5 => return straight(entry),
+            _ => return straight(exit.or(entry).expect("path leads somewhere")),
+        };
+
+        let pivot = (unit(exit) - unit(entry)) * CELL_SIZE / 2.0;
+        let (radius, sweep) = if exit == entry.right() {
+            (CELL_SIZE / 2.0 - lane, -PI / 2.0)
+        } else {
+            (CELL_SIZE / 2.0 + lane, PI / 2.0)
+        };
+        let around = angle(exit.opposite()) + sweep * t;
+        let offset = pivot + Vec2::new(around.cos(), around.sin()) * radius;
+        (offset, angle(entry) + sweep * t)
+    }
+}
+
+pub fn draw_recipes(draw: &Draw, window: Rect, items: &[Item], style: &Style) {
+    for row in recipe_rows(window, items) {
+        draw_item(draw, row.result_frame, row.item, style);
+        if row.is_point {
+            draw.text("+1")
+                .xy(row.result_frame.xy())
+                .wh(row.result_frame.wh())
+                .align_text_middle_y()
+                .font_size((ITEM_RECIPE_SIZE * 0.65) as u32)
+                .color(style.theme.label);
+        }
+
+        for [from, to] in row.pluses.into_iter().flatten().chain(row.equals) {
+            draw.line()
+                .points(from, to)
+                .color(style.theme.recipe_symbols);
+        }
+        for (frame, component) in row.components {
+            draw_item(draw, frame, component, style);
+        }
+
+        if let Some((clock_frame, [from, to])) = row.clock {
+            draw.ellipse()
+                .xy(clock_frame.xy())
+                .wh(clock_frame.wh())
+                .no_fill()
+                .stroke(style.theme.recipe_symbols)
+                .stroke_weight(2.0 * SIZE_UNIT);
+            draw.line()
+                .points(from, to)
+                .color(style.theme.recipe_symbols)
+                .stroke_weight(2.0 * SIZE_UNIT);
+        }
+    }
+}
+
+/// Where one recipe of the recipe panel goes: the result, an equals sign, then the components
+/// with plus signs between them
+pub struct RecipeRow<'a> {
+    pub item: &'a Item,
+    pub result_frame: Rect,
+    /// Gets a "+1" over it, being the item submitted for points
+    pub is_point: bool,
+    /// Bars of the equals sign
+    pub equals: [[Vec2; 2]; 2],
+    /// Bars of each plus sign
+    pub pluses: Vec<[[Vec2; 2]; 2]>,
+    pub components: Vec<(Rect, &'a Item)>,
+    /// A clock face and its hand instead of components, for raw resources
+    pub clock: Option<(Rect, [Vec2; 2])>,
 }
 
-pub fn draw_recipes(draw: &Draw, window: Rect, items: &[Item]) {
-    #[allow(clippy::iter_count)]
-    let item_count = items
+/// One row per recipe, in the top right corner of the window
+pub fn recipe_rows<'a>(window: Rect, items: &'a [Item]) -> Vec<RecipeRow<'a>> {
+    let point = items.last().unwrap();
+    let rows: Vec<(&Item, &Recipe)> = items
         .iter()
-        // .filter(|i| !i.components.is_empty())
-        .count();
-    let max_components = items
+        .flat_map(|i| i.recipes.iter().map(move |r| (i, r)))
+        .collect();
+    let max_components = rows
         .iter()
-        .map(|i| i.components.values().sum::<usize>())
+        .map(|(_, r)| r.components.values().sum::<usize>())
         .max()
         .unwrap();
-    let recipe_frame_contents_size =
-        Vec2::new(((max_components * 2) + 1) as f32, item_count as f32)
-            * Vec2::new(ITEM_RECIPE_SIZE, RECIPE_ROW_HEIGHT);
+    let row_width = ((max_components * 2) + 1) as f32 * ITEM_RECIPE_SIZE;
 
-    for (line, item) in items
-        .iter()
-        // .filter(|i| !i.components.is_empty())
-        .enumerate()
-    {
-        let row_frame = Rect::from_w_h(recipe_frame_contents_size.x, RECIPE_ROW_HEIGHT)
+    let mut layout = vec![];
+    for (line, (item, recipe)) in rows.into_iter().enumerate() {
+        let row_frame = Rect::from_w_h(row_width, RECIPE_ROW_HEIGHT)
             .align_top_of(window)
             .align_right_of(window)
             .shift_y(-RECIPE_ROW_HEIGHT * line as f32)
@@ -272,103 +640,581 @@ pub fn draw_recipes(draw: &Draw, window: Rect, items: &[Item]) {
             .align_left_of(row_frame)
             .shift_x(-ITEM_RECIPE_SIZE / 3.0);
 
-        draw.rect()
-            .xy(result_frame.xy())
-            .wh(result_frame.wh())
-            .color(soften(item.color))
-            .stroke(item.color);
-
-        // Point
-        if line + 1 == item_count {
-            draw.text("+1")
-                .xy(result_frame.xy())
-                .wh(result_frame.wh())
-                .align_text_middle_y()
-                .font_size((ITEM_RECIPE_SIZE * 0.65) as u32)
-                .color(BLACK);
-        }
-
         let equals_frame = result_frame
             .shift_x(ITEM_RECIPE_SIZE)
             .pad(ITEM_RECIPE_SIZE / 6.0);
-        draw.line()
-            .y(-ITEM_RECIPE_SIZE / 6.0)
-            .points(equals_frame.mid_left(), equals_frame.mid_right())
-            .color(WHITE);
-        draw.line()
-            .y(ITEM_RECIPE_SIZE / 6.0)
-            .points(equals_frame.mid_left(), equals_frame.mid_right())
-            .color(WHITE);
+        let equals = [-ITEM_RECIPE_SIZE / 6.0, ITEM_RECIPE_SIZE / 6.0].map(|y| {
+            [equals_frame.mid_left(), equals_frame.mid_right()].map(|p| p + Vec2::new(0.0, y))
+        });
 
         let mut component_frame = result_frame;
-        let mut is_first = true;
-        for (component, &count) in &item.components {
+        let mut pluses = vec![];
+        let mut components = vec![];
+        for (component, &count) in &recipe.components {
             for _ in 0..count {
                 component_frame = component_frame.shift_x(ITEM_RECIPE_SIZE);
-                if !is_first {
+                if !components.is_empty() {
                     let plus_frame = component_frame.pad(ITEM_RECIPE_SIZE / 6.0);
-                    draw.line()
-                        .points(plus_frame.mid_left(), plus_frame.mid_right())
-                        .color(WHITE);
-                    draw.line()
-                        .points(plus_frame.mid_top(), plus_frame.mid_bottom())
-                        .color(WHITE);
+                    pluses.push([
+                        [plus_frame.mid_left(), plus_frame.mid_right()],
+                        [plus_frame.mid_top(), plus_frame.mid_bottom()],
+                    ]);
                 }
-                is_first = false;
-
                 component_frame = component_frame.shift_x(ITEM_RECIPE_SIZE);
-
-                draw.rect()
-                    .xy(component_frame.xy())
-                    .wh(component_frame.wh())
-                    .color(soften(component.color))
-                    .stroke(component.color);
+                components.push((component_frame, component));
             }
         }
 
         // Spawned item
-        if item.components.is_empty() {
-            component_frame = component_frame.shift_x(ITEM_RECIPE_SIZE * 2.0);
-            draw.ellipse()
-                .xy(component_frame.xy())
-                .wh(component_frame.wh())
-                .no_fill()
-                .stroke(WHITE)
-                .stroke_weight(2.0 * SIZE_UNIT);
-            draw.line()
-                .points(
-                    component_frame.xy(),
-                    component_frame.xy()
-                        + (component_frame.mid_right() - component_frame.xy()).rotate(1.0) * 0.75,
-                )
-                .color(WHITE)
-                .stroke_weight(2.0 * SIZE_UNIT);
-        }
+        let clock = recipe.components.is_empty().then(|| {
+            let clock_frame = component_frame.shift_x(ITEM_RECIPE_SIZE * 2.0);
+            let hand = (clock_frame.mid_right() - clock_frame.xy()).rotate(1.0) * 0.75;
+            (clock_frame, [clock_frame.xy(), clock_frame.xy() + hand])
+        });
+
+        layout.push(RecipeRow {
+            item,
+            result_frame,
+            is_point: item == point,
+            equals,
+            pluses,
+            components,
+            clock,
+        });
     }
+    layout
+}
+
+/// Room the recipe panel takes, with space for the results sticking out on the left
+pub fn recipe_panel_size(items: &[Item]) -> Vec2 {
+    let rows = items.iter().map(|i| i.recipes.len()).sum::<usize>();
+    let max_components = items
+        .iter()
+        .flat_map(|i| &i.recipes)
+        .map(|r| r.components.values().sum::<usize>())
+        .max()
+        .unwrap_or(0);
+    Vec2::new(
+        ((max_components * 2) + 2) as f32 * ITEM_RECIPE_SIZE,
+        rows as f32 * RECIPE_ROW_HEIGHT,
+    )
 }
 
 pub fn draw_score(draw: &Draw, screen: Rect, model: &Model) {
     let score_frame = Rect::from_w_h(200.0, 100.0).bottom_right_of(screen.pad(100.0));
-    draw.text(&format!("{}", model.score))
+    draw.text(&format!("{}", model.game.score))
         .xy(score_frame.xy())
         .wh(score_frame.wh())
         .font_size(72)
         .align_text_bottom()
-        .right_justify();
+        .right_justify()
+        .color(model.style.theme.text);
+}
+
+pub fn draw_balance(draw: &Draw, screen: Rect, model: &Model) {
+    let score_frame = Rect::from_w_h(200.0, 100.0).bottom_right_of(screen.pad(100.0));
+    let balance_frame = Rect::from_w_h(300.0, 100.0).left_of(score_frame);
+    draw.text(&format!("${}", model.game.balance))
+        .xy(balance_frame.xy())
+        .wh(balance_frame.wh())
+        .font_size(48)
+        .align_text_bottom()
+        .right_justify()
+        .color(model.style.theme.text);
+}
+
+/// Load on the network, red while in a brownout
+pub fn draw_power(draw: &Draw, screen: Rect, power: &PowerNetwork, style: &Style) {
+    let power_frame = Rect::from_w_h(400.0, 50.0)
+        .bottom_left_of(screen.pad(50.0))
+        .shift_y(50.0);
+    let color = if power.satisfaction() < 1.0 {
+        style.theme.invalid
+    } else {
+        style.theme.text
+    };
+    draw.text(&format!("Power {:.0}/{:.0}", power.demand, power.supply))
+        .xy(power_frame.xy())
+        .wh(power_frame.wh())
+        .font_size(32)
+        .align_text_bottom()
+        .left_justify()
+        .color(color);
+}
+
+pub fn draw_tool(draw: &Draw, screen: Rect, model: &Model) {
+    let tool = model.editor.tool;
+    let cost = tool
+        .grid_item(&model.game.items)
+        .map(|i| format!(" (${})", i.cost()))
+        .unwrap_or_default();
+    let clipboard = match (tool, &model.editor.clipboard) {
+        (editor::Tool::Paste, Some(c)) if !c.name.is_empty() => format!(" {}", c.name),
+        _ => String::new(),
+    };
+    let tool_frame = Rect::from_w_h(400.0, 50.0).bottom_left_of(screen.pad(50.0));
+    draw.text(&format!("{}{}{}", tool.name(), cost, clipboard))
+        .xy(tool_frame.xy())
+        .wh(tool_frame.wh())
+        .font_size(32)
+        .align_text_bottom()
+        .left_justify()
+        .color(model.style.theme.text);
+}
+
+/// Replay progress and controls
+pub fn draw_playback(draw: &Draw, screen: Rect, model: &Model, playback: &Playback) {
+    let seconds = |tick: u64| tick as f64 * TICK_LENGTH;
+    let paused = if model.paused { " (paused)" } else { "" };
+    let frame = Rect::from_w_h(800.0, 50.0).bottom_left_of(screen.pad(50.0));
+    draw.text(&format!(
+        "Replay {:.1}s / {:.1}s x{}{paused}",
+        seconds(model.game.tick),
+        seconds(playback.replay.final_tick),
+        model.speed,
+    ))
+    .xy(frame.xy())
+    .wh(frame.wh())
+    .font_size(32)
+    .align_text_bottom()
+    .left_justify()
+    .color(model.style.theme.text);
+}
+
+/// A box in the middle of the screen with a title, lines of text, and rows of names and values
+/// with the selected one marked
+pub fn draw_menu(
+    draw: &Draw,
+    screen: Rect,
+    theme: &Theme,
+    title: &str,
+    notes: &[String],
+    rows: &[(String, String)],
+    selected: usize,
+) {
+    let row_count = 1 + notes.len() + rows.len();
+    let menu_frame = Rect::from_w_h(MENU_WIDTH, MENU_ROW_HEIGHT * row_count as f32)
+        .pad(-MENU_ROW_HEIGHT)
+        .middle_of(screen);
+    draw.rect()
+        .xy(menu_frame.xy())
+        .wh(menu_frame.wh())
+        .color(theme.background)
+        .stroke(theme.text)
+        .stroke_weight(2.0 * SIZE_UNIT);
+
+    let row_frame =
+        Rect::from_w_h(MENU_WIDTH, MENU_ROW_HEIGHT).mid_top_of(menu_frame.pad(MENU_ROW_HEIGHT));
+    draw.text(title)
+        .xy(row_frame.xy())
+        .wh(row_frame.wh())
+        .font_size(32)
+        .color(theme.text);
+    for (i, note) in notes.iter().enumerate() {
+        let row_frame = row_frame.shift_y(-MENU_ROW_HEIGHT * (i + 1) as f32);
+        draw.text(note)
+            .xy(row_frame.xy())
+            .wh(row_frame.wh())
+            .font_size(24)
+            .color(theme.text);
+    }
+    for (i, (name, value)) in rows.iter().enumerate() {
+        let row_frame = row_frame.shift_y(-MENU_ROW_HEIGHT * (notes.len() + i + 1) as f32);
+        let marker = if i == selected { "> " } else { "" };
+        draw.text(&format!("{marker}{name}"))
+            .xy(row_frame.xy())
+            .wh(row_frame.wh())
+            .font_size(24)
+            .left_justify()
+            .color(theme.text);
+        draw.text(value)
+            .xy(row_frame.xy())
+            .wh(row_frame.wh())
+            .font_size(24)
+            .right_justify()
+            .color(theme.text);
+    }
+}
+
+/// Every setting and its value
+pub fn draw_settings_menu(draw: &Draw, screen: Rect, model: &Model, menu: &Menu) {
+    let rows: Vec<(String, String)> = Setting::ALL
+        .into_iter()
+        .map(|s| (s.name().to_string(), s.value(&model.settings)))
+        .collect();
+    draw_menu(
+        draw,
+        screen,
+        &model.style.theme,
+        "Settings",
+        &["Up and down to pick, left and right to change".to_string()],
+        &rows,
+        menu.selected,
+    );
+}
+
+/// The screen's menu, with a summary of the game on the results screen
+pub fn draw_screen(draw: &Draw, screen: Rect, model: &Model) {
+    let Some(menu) = model.screen.menu() else {
+        return;
+    };
+    let rows: Vec<(String, String)> = model
+        .screen
+        .entries(model.playback.is_some())
+        .iter()
+        .map(|e| e.text(model))
+        .collect();
+    let notes = match model.screen {
+        Screen::Results(_) => {
+            let game = &model.game;
+            let minutes = game.tick as f64 * TICK_LENGTH / 60.0;
+            let rate = if minutes > 0.0 {
+                game.score as f64 / minutes
+            } else {
+                0.0
+            };
+            vec![
+                format!("Score {} in {}", game.score, screen::duration(game.tick)),
+                format!("{rate:.1} points per minute, ${} left", game.balance),
+            ]
+        }
+        Screen::Load(_, ref saves) if saves.is_empty() => vec!["No saved games".to_string()],
+        _ => vec![],
+    };
+    draw_menu(
+        draw,
+        screen,
+        &model.style.theme,
+        model.screen.title(),
+        &notes,
+        &rows,
+        menu.selected,
+    );
+}
+
+/// Every command and what it's bound to, in two columns
+pub fn draw_help(draw: &Draw, screen: Rect, model: &Model) {
+    let theme = &model.style.theme;
+    let rows = Command::ALL.len().div_ceil(2);
+    let help_frame = Rect::from_w_h(HELP_COLUMN_WIDTH * 2.0, HELP_ROW_HEIGHT * rows as f32)
+        .pad(-HELP_ROW_HEIGHT)
+        .middle_of(screen);
+    draw.rect()
+        .xy(help_frame.xy())
+        .wh(help_frame.wh())
+        .color(theme.background)
+        .stroke(theme.text)
+        .stroke_weight(2.0 * SIZE_UNIT);
+
+    let row_frame = Rect::from_w_h(HELP_COLUMN_WIDTH, HELP_ROW_HEIGHT)
+        .top_left_of(help_frame.pad(HELP_ROW_HEIGHT))
+        .pad_left(HELP_ROW_HEIGHT / 2.0);
+    for (i, command) in Command::ALL.into_iter().enumerate() {
+        let row_frame = row_frame
+            .shift_x(HELP_COLUMN_WIDTH * (i / rows) as f32)
+            .shift_y(-HELP_ROW_HEIGHT * (i % rows) as f32);
+        let bindings: Vec<String> = model.bindings.of(command).map(|b| b.to_string()).collect();
+        draw.text(&format!("{}: {}", command.name(), bindings.join(", ")))
+            .xy(row_frame.xy())
+            .wh(row_frame.wh())
+            .font_size(18)
+            .left_justify()
+            .color(theme.text);
+    }
+}
+
+/// Items per minute each item is made at, against what its buildings could make at their tiers
+pub fn draw_statistics(draw: &Draw, screen: Rect, game: &Game, style: &Style) {
+    let theme = &style.theme;
+    let rows = game.statistics.throughput(&game.grid);
+    let frame = Rect::from_w_h(
+        STATISTICS_WIDTH,
+        STATISTICS_ROW_HEIGHT * (rows.len() + 1) as f32,
+    )
+    .bottom_right_of(screen.pad(50.0));
+    draw.rect()
+        .xy(frame.xy())
+        .wh(frame.pad(-STATISTICS_ROW_HEIGHT / 2.0).wh())
+        .color(theme.background)
+        .stroke(theme.text)
+        .stroke_weight(2.0 * SIZE_UNIT);
+
+    let row_frame = Rect::from_w_h(STATISTICS_WIDTH, STATISTICS_ROW_HEIGHT).mid_top_of(frame);
+    let header = ("Item".to_string(), "Made / Capacity per minute".to_string());
+    let lines = rows.iter().map(|row| {
+        (
+            format!("{} x{}", row.item.name, row.buildings),
+            format!("{:.1} / {:.1}", row.produced, row.capacity),
+        )
+    });
+    for (i, (name, rate)) in std::iter::once(header).chain(lines).enumerate() {
+        let row_frame = row_frame.shift_y(-STATISTICS_ROW_HEIGHT * i as f32);
+        draw.text(&name)
+            .xy(row_frame.xy())
+            .wh(row_frame.wh())
+            .font_size(20)
+            .left_justify()
+            .color(theme.text);
+        draw.text(&rate)
+            .xy(row_frame.xy())
+            .wh(row_frame.wh())
+            .font_size(20)
+            .right_justify()
+            .color(theme.text);
+    }
+}
+
+pub fn draw_selection(draw: &Draw, (a, b): (Position, Position), style: &Style) {
+    let (a, b): (Vec2, Vec2) = (a.into(), b.into());
+    let rect = Rect::from_corners(a, b).pad(-CELL_SIZE / 2.0);
+    draw.rect()
+        .xy(rect.xy())
+        .wh(rect.wh())
+        .color(style.theme.selection)
+        .stroke(style.theme.cursor)
+        .stroke_weight(2.0 * SIZE_UNIT);
+}
+
+/// Ghost of the clipboard with its bottom left corner on the hovered cell
+pub fn draw_paste_preview(
+    draw: &Draw,
+    game: &Game,
+    style: &Style,
+    clipboard: &Blueprint,
+    anchor: Position,
+) {
+    for (position, tool) in clipboard.placed_at(anchor, &game.items) {
+        let draw = draw.xy(position.into());
+        let grid_item = tool.and_then(|tool| tool.grid_item(&game.items));
+        if let Some(grid_item) = grid_item {
+            if !game.grid.grid_items.contains_key(&position) {
+                grid_item.draw_rail(&draw, style);
+                grid_item.draw(&draw, style);
+            }
+        }
+        let usable = tool.is_some_and(|tool| editor::can_use(game, tool, position));
+        let color = if usable {
+            style.theme.cursor
+        } else {
+            style.theme.invalid
+        };
+        draw.rect()
+            .w_h(CELL_SIZE, CELL_SIZE)
+            .no_fill()
+            .stroke(color)
+            .stroke_weight(SIZE_UNIT);
+    }
+}
+
+/// Preview of the current tool on the hovered cell, outlined red if it can't be used there
+pub fn draw_cursor(draw: &Draw, model: &Model, position: Position) {
+    let tool = model.editor.tool;
+    if let Some(grid_item) = tool.grid_item(&model.game.items) {
+        if !model.game.grid.grid_items.contains_key(&position) {
+            grid_item.draw_rail(draw, &model.style);
+            grid_item.draw(draw, &model.style);
+        }
+    }
+
+    let color = if editor::can_use(&model.game, tool, position) {
+        model.style.theme.cursor
+    } else {
+        model.style.theme.invalid
+    };
+    draw.rect()
+        .w_h(CELL_SIZE, CELL_SIZE)
+        .no_fill()
+        .stroke(color)
+        .stroke_weight(2.0 * SIZE_UNIT);
 }
 
 // === Utils ===
 
-fn soften(color: Srgb) -> Srgb {
+/// An item's icon, filling the frame
+pub fn draw_item(draw: &Draw, frame: Rect, item: &Item, style: &Style) {
+    let color = style.color(item);
+    draw.polygon()
+        .stroke(color)
+        .stroke_weight(SIZE_UNIT)
+        .points(item_outline(frame, item.shape))
+        .color(soften(color));
+
+    if style.labels {
+        draw.text(&item.label)
+            .xy(frame.xy())
+            .wh(frame.wh() * 2.0)
+            .align_text_middle_y()
+            .font_size((frame.h() * 0.6).max(1.0) as u32)
+            .color(style.theme.label);
+    }
+}
+
+/// Corners of an item icon filling the frame
+pub fn item_outline(frame: Rect, shape: Shape) -> Vec<Vec2> {
+    let radius = frame.w().min(frame.h()) / 2.0;
+    match shape {
+        Shape::Square => vec![
+            frame.top_left(),
+            frame.top_right(),
+            frame.bottom_right(),
+            frame.bottom_left(),
+        ],
+        Shape::Circle => regular_polygon(frame.xy(), radius, 32),
+        Shape::Triangle => vec![frame.mid_top(), frame.bottom_right(), frame.bottom_left()],
+        Shape::Diamond => vec![
+            frame.mid_top(),
+            frame.mid_right(),
+            frame.mid_bottom(),
+            frame.mid_left(),
+        ],
+        Shape::Hexagon => regular_polygon(frame.xy(), radius, 6),
+        Shape::Star => regular_polygon(frame.xy(), radius, 10)
+            .into_iter()
+            .enumerate()
+            .map(|(i, p)| match i % 2 {
+                0 => p,
+                _ => frame.xy() + (p - frame.xy()) * 0.45,
+            })
+            .collect(),
+        Shape::Cross => {
+            let arm = frame.pad(frame.w().min(frame.h()) / 3.0);
+            vec![
+                Vec2::new(arm.left(), frame.top()),
+                Vec2::new(arm.right(), frame.top()),
+                arm.top_right(),
+                Vec2::new(frame.right(), arm.top()),
+                Vec2::new(frame.right(), arm.bottom()),
+                arm.bottom_right(),
+                Vec2::new(arm.right(), frame.bottom()),
+                Vec2::new(arm.left(), frame.bottom()),
+                arm.bottom_left(),
+                Vec2::new(frame.left(), arm.bottom()),
+                Vec2::new(frame.left(), arm.top()),
+                arm.top_left(),
+            ]
+        }
+    }
+}
+
+/// Where a building sits in its cell, pushed back from the rail it faces
+pub fn building_frame(direction: Direction, theme: &Theme) -> Rect {
+    let building_size = BUILDING_SIZE * theme.building_scale;
+    let offset = -(CELL_SIZE - BUILDING_SIZE) / 4.0;
+    let center = Vec2::new(offset, 1.0).rotate(direction.into());
+    Rect::from_xy_wh(center, (building_size, building_size).into())
+}
+
+/// How much bigger a building is drawn while it sends out an item
+pub fn extra_size(spawn_timer: f64) -> f64 {
+    // Expand
+    lerp(
+        spawn_timer,
+        0.0,
+        ITEM_SPAWN_ANIMATION_TIME - ITEM_SPAWN_ANIMATION_TIME_SHRINK,
+        0.0,
+        LOADING_BAR_WEIGHT,
+    )
+    .unwrap_or(0.0)
+    // Shrink
+        + lerp(
+            spawn_timer,
+            ITEM_SPAWN_ANIMATION_TIME - ITEM_SPAWN_ANIMATION_TIME_SHRINK,
+            ITEM_SPAWN_ANIMATION_TIME,
+            LOADING_BAR_WEIGHT,
+            0.0,
+        )
+        .unwrap_or(0.0)
+}
+
+/// Icon frames of the items a building holds, in rows from the top left
+pub fn contents_frames(
+    building_frame: Rect,
+    contents: &BTreeMap<Item, usize>,
+) -> Vec<(Rect, &Item)> {
+    let items_frame = building_frame.pad(5.0 * SIZE_UNIT);
+    let mut position = (0, 0);
+    // Grow the square when upgraded buildings buffer more items
+    let total = contents.values().sum::<usize>();
+    let side = INVENTORY_ITEM_SQUARE_SIDE.max((total as f32).sqrt().ceil() as usize);
+    let item_frame = Rect::from_wh(items_frame.wh() / side as f32).top_left_of(items_frame);
+    let mut frames = vec![];
+    for (item, &count) in contents.iter() {
+        for _ in 0..count {
+            let position_px = Vec2::new(position.0 as f32, position.1 as f32);
+            let item_frame = item_frame.shift(item_frame.wh() * position_px);
+            frames.push((item_frame.pad(2.0 * SIZE_UNIT), item));
+
+            position.0 += 1;
+            if position.0 >= side {
+                position.1 -= 1;
+                position.0 = 0;
+            }
+        }
+    }
+    frames
+}
+
+/// Circle around a spawner that closes as its timer runs, `completion` going 0-->1
+pub fn loading_arc_points(building_frame: Rect, completion: f32) -> Vec<Vec2> {
+    let arc = Arc {
+        center: (building_frame.x(), building_frame.y()).into(),
+        radii: (building_frame.w() / 2.0, building_frame.h() / 2.0).into(),
+        start_angle: Angle::radians(0.0),
+        sweep_angle: lerp(completion, 0.0, 1.0, Angle::zero(), Angle::two_pi())
+            .unwrap_or(Angle::two_pi())
+            * 1.03, // fix visually idk why
+        x_rotation: Angle::radians(0.0),
+    };
+    arc.flattened(0.1).map(|p| Vec2::from((p.x, p.y))).collect()
+}
+
+pub fn regular_polygon(center: Vec2, radius: f32, sides: usize) -> Vec<Vec2> {
+    (0..sides)
+        .map(|i| {
+            let angle = PI * 2.0 * i as f32 / sides as f32;
+            center + Vec2::new(angle.cos(), angle.sin()) * radius
+        })
+        .collect()
+}
+
+/// Vector of length one pointing in the direction
+pub fn unit(direction: Direction) -> Vec2 {
+    let angle = angle(direction);
+    Vec2::new(angle.cos(), angle.sin())
+}
+
+pub fn angle(direction: Direction) -> f32 {
+    direction.into()
+}
+
+pub fn soften(color: Srgb) -> Srgb {
     const C: f32 = 0.8;
     let mut color: Hsv = color.into();
     color.saturation *= C;
     color.into()
 }
 
-fn draw_loading_square_frame(draw: &Draw, completion: f32, wh: f32) {
+fn draw_loading_square_frame(draw: &Draw, completion: f32, wh: f32, theme: &Theme) {
+    let points = loading_square_points(completion, wh);
+    let mut path = Path::builder();
+    path = path.begin(points[0]);
+    for point in &points[1..] {
+        path = path.line_to(*point);
+    }
+    path.inner_mut().end(false);
+
+    draw.path()
+        .stroke()
+        .weight(theme.loading_bar_width)
+        .end_cap_round()
+        .color(theme.loading_bar)
+        .events(path.build().iter());
+}
+
+/// Square around a crafter that closes as its timer runs, starting and ending on the right side
+pub fn loading_square_points(completion: f32, wh: f32) -> Vec<Vec2> {
     let rect = Rect::from_w_h(wh, wh);
-    let points = vec![
+    let points = [
         (rect.mid_right(), 0.0),
         (rect.top_right(), 0.125),
         (rect.top_left(), 0.125 + 0.25),
@@ -377,8 +1223,7 @@ fn draw_loading_square_frame(draw: &Draw, completion: f32, wh: f32) {
         (rect.mid_right(), 1.0),
     ];
 
-    let mut path = Path::builder();
-    path = path.begin(points[0].0);
+    let mut path = vec![points[0].0];
     for window in points.windows(2) {
         let (from, start) = window[0];
         let (to, end) = window[1];
@@ -386,19 +1231,12 @@ fn draw_loading_square_frame(draw: &Draw, completion: f32, wh: f32) {
         if completion < start {
             break;
         }
-        path = path.line_to(lerp(completion, start, end, from, to).unwrap_or(to));
+        path.push(lerp(completion, start, end, from, to).unwrap_or(to));
     }
-    path.inner_mut().end(false);
-
-    draw.path()
-        .stroke()
-        .weight(2.0 * LOADING_BAR_WEIGHT as f32)
-        .end_cap_round()
-        .color(LOADING_BAR_COLOR)
-        .events(path.build().iter());
+    path
 }
 
-fn lerp<R, V>(val: R, range_start: R, range_end: R, val_start: V, val_end: V) -> Option<V>
+pub fn lerp<R, V>(val: R, range_start: R, range_end: R, val_start: V, val_end: V) -> Option<V>
 where
     R: std::cmp::PartialOrd + std::ops::Sub<Output = R> + std::ops::Div<Output = R> + Copy,
     V: std::ops::Sub<Output = V> + std::ops::Mul<R, Output = V> + std::ops::Add<Output = V> + Copy,